@@ -0,0 +1,63 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// `$XDG_CACHE_HOME/claude-statusline`, falling back to `~/.cache` when
+/// `XDG_CACHE_HOME` is unset
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+    Some(cache_home.join("claude-statusline"))
+}
+
+/// Cache file name for a key that comes from outside, e.g. a session id.
+/// Anything that could escape the cache dir is replaced.
+pub fn file_name(key: &str, extension: &str) -> String {
+    let key: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}.{}", key, extension)
+}
+
+/// Writes through a temporary file and a rename, so concurrent statusline
+/// runs never read a half-written cache entry
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", std::process::id()));
+    let temp = PathBuf::from(temp);
+
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_is_sanitized() {
+        assert_eq!(file_name("3f2a-9c_01", "json"), "3f2a-9c_01.json");
+        assert_eq!(file_name("../../etc/passwd", "json"), "______etc_passwd.json");
+    }
+
+    #[test]
+    fn test_write_atomic_creates_parent_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a").join("b.json");
+        write_atomic(&path, b"{}").unwrap();
+        write_atomic(&path, b"[]").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,198 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::config::ColorMode;
+use crate::dialect::Dialect;
+
+/// What a run prints
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// The colored statusline
+    #[default]
+    Text,
+    /// The collected status as versioned JSON, for other tools
+    Json,
+}
+
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputMode::Text),
+            "json" => Ok(OutputMode::Json),
+            _ => Err(format!("invalid output `{}`: expected `text` or `json`", s)),
+        }
+    }
+}
+
+/// Command-line flags. Claude Code runs the binary without arguments, so
+/// every flag is optional.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args {
+    pub config: Option<PathBuf>,
+    /// `--width`: columns the line may take, overriding the config and
+    /// `COLUMNS`
+    pub width: Option<usize>,
+    /// `--color always|auto|never`, overriding the config
+    pub color: Option<ColorMode>,
+    /// `--output text|json`
+    pub output: OutputMode,
+    /// `--format ansi|tmux|zsh|bash`: how the text line writes its colors
+    pub format: Dialect,
+    /// `--theme`: a built-in theme name or theme file, overriding the config
+    pub theme: Option<String>,
+    /// `--no-input`: don't wait for a Claude Code session on stdin, for
+    /// tools that run the binary with stdin left open
+    pub no_input: bool,
+    /// `daemon`: run the background VCS refresh daemon instead of printing
+    /// a statusline. It takes none of the flags above.
+    pub daemon: bool,
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        // The first flag given, which only matters to the statusline
+        let mut line_flag = None;
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("missing value for `{}`", flag))
+            };
+
+            if flag != "daemon" {
+                line_flag.get_or_insert_with(|| flag.clone());
+            }
+
+            match flag.as_str() {
+                "--config" => parsed.config = Some(PathBuf::from(value()?)),
+                "--width" => {
+                    let width = value()?;
+                    match width.parse() {
+                        Ok(width) if width > 0 => parsed.width = Some(width),
+                        _ => return Err(format!("invalid value `{}` for `--width`: expected a positive number", width)),
+                    }
+                }
+                "--color" => parsed.color = Some(value()?.parse()?),
+                "--theme" => parsed.theme = Some(value()?),
+                "--output" => parsed.output = value()?.parse()?,
+                "--format" => parsed.format = value()?.parse()?,
+                "--no-input" => parsed.no_input = true,
+                "daemon" => parsed.daemon = true,
+                _ => return Err(format!("unknown argument `{}`", arg)),
+            }
+        }
+
+        if let (true, Some(flag)) = (parsed.daemon, line_flag) {
+            return Err(format!("`{}` can't be used with `daemon`", flag));
+        }
+
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_no_arguments() {
+        assert_eq!(parse(&[]), Ok(Args::default()));
+    }
+
+    #[test]
+    fn test_config_flag() {
+        let expected = Some(PathBuf::from("/tmp/statusline.toml"));
+        assert_eq!(parse(&["--config", "/tmp/statusline.toml"]).unwrap().config, expected);
+        assert_eq!(parse(&["--config=/tmp/statusline.toml"]).unwrap().config, expected);
+    }
+
+    #[test]
+    fn test_width_flag() {
+        assert_eq!(parse(&["--width", "80"]).unwrap().width, Some(80));
+        assert_eq!(parse(&["--width=120"]).unwrap().width, Some(120));
+        assert_eq!(
+            parse(&["--width", "0"]),
+            Err("invalid value `0` for `--width`: expected a positive number".to_string())
+        );
+        assert!(parse(&["--width", "wide"]).is_err());
+    }
+
+    #[test]
+    fn test_color_flag() {
+        assert_eq!(parse(&["--color", "never"]).unwrap().color, Some(ColorMode::Never));
+        assert_eq!(parse(&["--color=always"]).unwrap().color, Some(ColorMode::Always));
+        assert_eq!(
+            parse(&["--color", "yes"]),
+            Err("invalid color mode `yes`: expected `always`, `auto` or `never`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_theme_flag() {
+        assert_eq!(parse(&["--theme", "gruvbox"]).unwrap().theme.as_deref(), Some("gruvbox"));
+        assert_eq!(parse(&["--theme=./mine.toml"]).unwrap().theme.as_deref(), Some("./mine.toml"));
+    }
+
+    #[test]
+    fn test_output_flag() {
+        assert_eq!(parse(&[]).unwrap().output, OutputMode::Text);
+        assert_eq!(parse(&["--output", "json"]).unwrap().output, OutputMode::Json);
+        assert_eq!(
+            parse(&["--output=yaml"]),
+            Err("invalid output `yaml`: expected `text` or `json`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_format_flag() {
+        assert_eq!(parse(&[]).unwrap().format, Dialect::Ansi);
+        assert_eq!(parse(&["--format", "tmux"]).unwrap().format, Dialect::Tmux);
+        assert_eq!(parse(&["--format=bash"]).unwrap().format, Dialect::Bash);
+        assert!(parse(&["--format", "fish"]).is_err());
+    }
+
+    #[test]
+    fn test_no_input_flag() {
+        assert!(!parse(&[]).unwrap().no_input);
+        assert!(parse(&["--output", "json", "--no-input"]).unwrap().no_input);
+    }
+
+    #[test]
+    fn test_daemon_subcommand() {
+        assert_eq!(parse(&["daemon"]), Ok(Args { daemon: true, ..Args::default() }));
+        // The daemon reads no config and draws no line
+        assert_eq!(
+            parse(&["daemon", "--config", "/tmp/statusline.toml"]),
+            Err("`--config` can't be used with `daemon`".to_string())
+        );
+        assert_eq!(
+            parse(&["--theme=gruvbox", "daemon"]),
+            Err("`--theme` can't be used with `daemon`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_missing_value() {
+        assert_eq!(parse(&["--config"]), Err("missing value for `--config`".to_string()));
+    }
+
+    #[test]
+    fn test_unknown_argument() {
+        assert_eq!(parse(&["--verbose"]), Err("unknown argument `--verbose`".to_string()));
+    }
+}
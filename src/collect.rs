@@ -0,0 +1,161 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+#[cfg(unix)]
+use crate::daemon;
+use crate::deadline::spawn;
+use crate::directory::format_directory;
+use crate::input::ClaudeInput;
+use crate::output::StatusLine;
+use crate::pricing::session_cost;
+use crate::transcript::{read_usage, read_usage_cached, TranscriptUsage};
+use crate::vcs::{get_vcs_status, VcsStatus};
+use crate::vcs_cache::VcsCache;
+
+/// Collects every segment for `input` and assembles the statusline. Slow
+/// collectors (VCS status, the transcript) run at the same time, each on its
+/// own thread, and whatever hasn't finished by `deadline` is left out.
+/// `cache_dir` holds the on-disk caches and the daemon's socket; without one
+/// everything is collected from scratch.
+pub fn collect(input: ClaudeInput, config: &Config, cache_dir: Option<&Path>, deadline: Instant) -> StatusLine {
+    let cache_dir = cache_dir.map(Path::to_path_buf);
+
+    let vcs = {
+        let (path, ttl, cache_dir) = (input.workspace.current_dir.clone(), config.vcs_cache_ttl(), cache_dir.clone());
+        spawn(move || vcs_status(&path, deadline, cache_dir.as_deref(), ttl))
+    };
+    let usage = {
+        let (path, session_id) = (input.transcript_path.clone(), input.session_id.clone());
+        spawn(move || transcript_usage(&path, &session_id, cache_dir.as_deref()))
+    };
+
+    let home_dir = env::var("HOME").ok();
+    let directory = format_directory(&input.workspace.current_dir, home_dir.as_deref());
+
+    // Determine output style
+    let output_style = if input.output_style.name != "default" && input.output_style.name != "null" {
+        Some(input.output_style.name.clone())
+    } else {
+        None
+    };
+
+    // Bounded by `deadline` itself, and returns only once any `git` or `jj`
+    // it gave up on is killed
+    let vcs = vcs.join().flatten();
+    let usage = usage.wait(deadline).flatten();
+    let context = usage.as_ref().and_then(|usage| usage.context(&input.model.id));
+    let cost = usage
+        .as_ref()
+        .and_then(|usage| session_cost(usage, &input.model.id, &config.pricing));
+    let tokens = usage.map(|usage| usage.total);
+
+    StatusLine {
+        current_dir: input.workspace.current_dir.clone(),
+        project_dir: input.workspace.project_dir.clone(),
+        directory,
+        vcs,
+        model_name: input.model.display_name.clone(),
+        output_style,
+        context,
+        tokens,
+        cost,
+        input: Some(input),
+        warning: None,
+    }
+}
+
+/// Without usable input, collects what the environment alone can tell: the
+/// working directory Claude Code started us in and its VCS status
+pub fn collect_without_input(config: &Config, cache_dir: Option<&Path>, deadline: Instant) -> StatusLine {
+    let cwd = env::current_dir()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .or_else(|_| env::var("PWD"))
+        .unwrap_or_default();
+    let home_dir = env::var("HOME").ok();
+
+    StatusLine {
+        directory: format_directory(&cwd, home_dir.as_deref()),
+        vcs: vcs_status(&cwd, deadline, cache_dir, config.vcs_cache_ttl()),
+        current_dir: cwd,
+        ..Default::default()
+    }
+}
+
+/// VCS status from the daemon when one is running, otherwise collected here
+/// (or reused from the on-disk cache)
+fn vcs_status(path: &str, deadline: Instant, cache_dir: Option<&Path>, ttl: Duration) -> Option<VcsStatus> {
+    #[cfg(unix)]
+    if let Some(status) = cache_dir.and_then(|dir| daemon::query(&daemon::socket_path(dir), path, deadline)) {
+        return status;
+    }
+
+    let cache = cache_dir.map(|dir| VcsCache::new(dir, ttl));
+    get_vcs_status(path, deadline, cache.as_ref())
+}
+
+/// Total token usage from the transcript; a missing or unreadable transcript
+/// just means no context or cost segments. Totals are cached per session so
+/// long transcripts aren't reparsed on every redraw.
+fn transcript_usage(path: &str, session_id: &str, cache_dir: Option<&Path>) -> Option<TranscriptUsage> {
+    if path.is_empty() {
+        return None;
+    }
+    let path = PathBuf::from(path);
+    match cache_dir {
+        Some(cache_dir) if !session_id.is_empty() => read_usage_cached(&path, session_id, cache_dir).ok(),
+        _ => read_usage(&path).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(cwd: &Path, transcript: &Path) -> ClaudeInput {
+        let json = serde_json::json!({
+            "session_id": "session-1",
+            "transcript_path": transcript,
+            "model": {"id": "claude-opus-4-1", "display_name": "Opus"},
+            "workspace": {"current_dir": cwd},
+            "output_style": {"name": "Learning"},
+        });
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_collect_assembles_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let transcript = dir.path().join("transcript.jsonl");
+        std::fs::write(
+            &transcript,
+            r#"{"type":"assistant","message":{"model":"claude-opus-4-1","usage":{"input_tokens":1000,"output_tokens":500}}}
+"#,
+        )
+        .unwrap();
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let status_line = collect(input(dir.path(), &transcript), &Config::default(), Some(cache_dir.path()), deadline);
+        assert_eq!(status_line.current_dir, dir.path().to_str().unwrap());
+        assert!(status_line.directory.ends_with(dir.path().file_name().unwrap().to_str().unwrap()));
+        assert_eq!(status_line.vcs, None);
+        assert_eq!(status_line.model_name, "Opus");
+        assert_eq!(status_line.output_style.as_deref(), Some("Learning"));
+        assert_eq!(status_line.tokens.map(|tokens| tokens.output_tokens), Some(500));
+        assert!(status_line.cost.is_some());
+        // Caches go where they're told, not into the real cache directory
+        assert!(cache_dir.path().join("transcripts").is_dir());
+    }
+
+    #[test]
+    fn test_collect_without_transcript() {
+        let dir = tempfile::tempdir().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let status_line = collect(input(dir.path(), Path::new("")), &Config::default(), None, deadline);
+        assert_eq!(status_line.tokens, None);
+        assert_eq!(status_line.context, None);
+        assert_eq!(status_line.cost, None);
+    }
+}
@@ -0,0 +1,386 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// A 24-bit color, written as `#rrggbb` in config files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Nearest color of the xterm 256-color palette: the 6×6×6 cube
+    /// (16–231) or the grey ramp (232–255)
+    pub fn to_ansi256(self) -> u8 {
+        let cube_index = |value: u8| CUBE_LEVELS.iter().enumerate().min_by_key(|(_, &level)| value.abs_diff(level)).unwrap().0;
+        let (r, g, b) = (cube_index(self.r), cube_index(self.g), cube_index(self.b));
+        let cube = Rgb::new(CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+
+        let average = (self.r as u32 + self.g as u32 + self.b as u32) / 3;
+        let grey_index = (average.saturating_sub(3) / 10).min(23) as u8;
+        let grey_level = 8 + 10 * grey_index;
+        let grey = Rgb::new(grey_level, grey_level, grey_level);
+
+        if self.distance(grey) < self.distance(cube) {
+            232 + grey_index
+        } else {
+            16 + 36 * r as u8 + 6 * g as u8 + b as u8
+        }
+    }
+
+    /// Nearest of the 16 basic ANSI colors, as xterm draws them by default
+    pub fn to_ansi16(self) -> u8 {
+        ANSI16.iter().enumerate().min_by_key(|(_, &color)| self.distance(color)).unwrap().0 as u8
+    }
+
+    fn distance(self, other: Rgb) -> u32 {
+        let channel = |a: u8, b: u8| (a.abs_diff(b) as u32).pow(2);
+        channel(self.r, other.r) + channel(self.g, other.g) + channel(self.b, other.b)
+    }
+}
+
+/// Channel values of the xterm 256-color cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// xterm's default basic colors, by ANSI number
+const ANSI16: [Rgb; 16] = [
+    Rgb::new(0, 0, 0),
+    Rgb::new(205, 0, 0),
+    Rgb::new(0, 205, 0),
+    Rgb::new(205, 205, 0),
+    Rgb::new(0, 0, 238),
+    Rgb::new(205, 0, 205),
+    Rgb::new(0, 205, 205),
+    Rgb::new(229, 229, 229),
+    Rgb::new(127, 127, 127),
+    Rgb::new(255, 0, 0),
+    Rgb::new(0, 255, 0),
+    Rgb::new(255, 255, 0),
+    Rgb::new(92, 92, 255),
+    Rgb::new(255, 0, 255),
+    Rgb::new(0, 255, 255),
+    Rgb::new(255, 255, 255),
+];
+
+/// Names of the basic ANSI colors, by number, as written in config files
+const ANSI16_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "bright_black",
+    "bright_red",
+    "bright_green",
+    "bright_yellow",
+    "bright_blue",
+    "bright_magenta",
+    "bright_cyan",
+    "bright_white",
+];
+
+/// How many colors the terminal can show
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ColorDepth {
+    #[default]
+    #[serde(rename = "truecolor")]
+    TrueColor,
+    #[serde(rename = "256")]
+    Ansi256,
+    #[serde(rename = "16")]
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// Guesses the depth from `COLORTERM` and `TERM`, looked up through
+    /// `var`. Without either there's nothing to go on, and 24-bit colors
+    /// are kept.
+    pub fn detect(var: &dyn Fn(&str) -> Option<String>) -> Self {
+        if matches!(var("COLORTERM").as_deref(), Some("truecolor" | "24bit")) {
+            return ColorDepth::TrueColor;
+        }
+        match var("TERM").filter(|term| !term.is_empty()) {
+            None => ColorDepth::TrueColor,
+            Some(term) if term.ends_with("-direct") => ColorDepth::TrueColor,
+            Some(term) if term.contains("256color") => ColorDepth::Ansi256,
+            Some(_) => ColorDepth::Ansi16,
+        }
+    }
+}
+
+/// A configured color: `#rrggbb`, or a table that also pins the exact color
+/// to use on terminals with fewer colors instead of the nearest one, e.g.
+/// `{ rgb = "#ff1493", ansi256 = 198, ansi16 = "bright_magenta" }`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub rgb: Rgb,
+    pub ansi256: Option<u8>,
+    /// 0–15
+    pub ansi16: Option<u8>,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self {
+            rgb: Rgb::new(r, g, b),
+            ansi256: None,
+            ansi16: None,
+        }
+    }
+
+    /// The same color, drawn as basic ANSI color `code` (0–15) at 16 colors
+    pub const fn with_ansi16(self, code: u8) -> Self {
+        Self {
+            ansi16: Some(code),
+            ..self
+        }
+    }
+
+    /// SGR parameters selecting this color as the foreground (or
+    /// background) at `depth`, e.g. `38;2;64;224;208`
+    pub fn sgr(&self, depth: ColorDepth, background: bool) -> String {
+        let layer = if background { 4 } else { 3 };
+        match depth {
+            ColorDepth::TrueColor => format!("{}8;2;{};{};{}", layer, self.rgb.r, self.rgb.g, self.rgb.b),
+            ColorDepth::Ansi256 => format!("{}8;5;{}", layer, self.ansi256.unwrap_or_else(|| self.rgb.to_ansi256())),
+            ColorDepth::Ansi16 => match self.ansi16.unwrap_or_else(|| self.rgb.to_ansi16()) {
+                code @ 0..=7 => format!("{}{}", layer, code),
+                code => format!("{}{}", layer + 6, code - 8),
+            },
+        }
+    }
+}
+
+impl From<Rgb> for Color {
+    fn from(rgb: Rgb) -> Self {
+        Self {
+            rgb,
+            ansi256: None,
+            ansi16: None,
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.ansi256.is_none() && self.ansi16.is_none() {
+            return self.rgb.serialize(serializer);
+        }
+        ColorTable {
+            rgb: self.rgb,
+            ansi256: self.ansi256,
+            ansi16: self.ansi16.map(|code| Ansi16(code as usize)),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::String(s) => s.parse::<Rgb>().map(Color::from).map_err(serde::de::Error::custom),
+            table => {
+                let table = ColorTable::deserialize(table).map_err(serde::de::Error::custom)?;
+                Ok(Color {
+                    rgb: table.rgb,
+                    ansi256: table.ansi256,
+                    ansi16: table.ansi16.map(|Ansi16(code)| code as u8),
+                })
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct ColorTable {
+    rgb: Rgb,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ansi256: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ansi16: Option<Ansi16>,
+}
+
+/// A basic ANSI color, written by name (`bright_magenta`) or number (13)
+struct Ansi16(usize);
+
+impl Serialize for Ansi16 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(ANSI16_NAMES[self.0])
+    }
+}
+
+impl<'de> Deserialize<'de> for Ansi16 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Written {
+            Number(u64),
+            Name(String),
+        }
+
+        let code = match Written::deserialize(deserializer)? {
+            Written::Number(code) => usize::try_from(code).ok().filter(|&code| code < 16),
+            Written::Name(name) => ANSI16_NAMES.iter().position(|&known| known == name),
+        };
+        code.map(Ansi16).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "invalid basic color: expected 0-15 or one of {}",
+                ANSI16_NAMES.join(", ")
+            ))
+        })
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s
+            .strip_prefix('#')
+            .ok_or_else(|| format!("invalid color `{}`: expected `#rrggbb`", s))?;
+
+        match hex::decode(digits).as_deref() {
+            Ok([r, g, b]) => Ok(Self::new(*r, *g, *b)),
+            _ => Err(format!("invalid color `{}`: expected `#rrggbb`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", hex::encode([self.r, self.g, self.b]))
+    }
+}
+
+impl Serialize for Rgb {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rgb {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::fake_env;
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!("#40e0d0".parse::<Rgb>(), Ok(Rgb::new(64, 224, 208)));
+        assert_eq!("#FF1493".parse::<Rgb>(), Ok(Rgb::new(255, 20, 147)));
+    }
+
+    #[test]
+    fn test_parse_invalid_colors() {
+        assert!("40e0d0".parse::<Rgb>().is_err());
+        assert!("#40e0d".parse::<Rgb>().is_err());
+        assert!("#40e0d0ff".parse::<Rgb>().is_err());
+        assert!("#zzzzzz".parse::<Rgb>().is_err());
+    }
+
+    #[test]
+    fn test_nearest_ansi256() {
+        assert_eq!(Rgb::new(0, 0, 0).to_ansi256(), 16);
+        assert_eq!(Rgb::new(255, 255, 255).to_ansi256(), 231);
+        assert_eq!(Rgb::new(255, 20, 147).to_ansi256(), 198); // Hot pink
+        assert_eq!(Rgb::new(64, 224, 208).to_ansi256(), 80); // Turquoise
+        assert_eq!(Rgb::new(96, 96, 96).to_ansi256(), 59); // The cube has a closer grey
+        assert_eq!(Rgb::new(80, 80, 80).to_ansi256(), 239); // Grey ramp
+        assert_eq!(Rgb::new(128, 128, 128).to_ansi256(), 244);
+    }
+
+    #[test]
+    fn test_nearest_ansi16() {
+        assert_eq!(Rgb::new(64, 224, 208).to_ansi16(), 6); // Cyan
+        assert_eq!(Rgb::new(255, 20, 147).to_ansi16(), 5); // Magenta
+        assert_eq!(Rgb::new(255, 140, 0).to_ansi16(), 3); // Yellow
+        assert_eq!(Rgb::new(255, 69, 0).to_ansi16(), 9); // Bright red
+        assert_eq!(Rgb::new(96, 96, 96).to_ansi16(), 8); // Bright black
+    }
+
+    #[test]
+    fn test_sgr_per_depth() {
+        let pink = Color::from(Rgb::new(255, 20, 147));
+        assert_eq!(pink.sgr(ColorDepth::TrueColor, false), "38;2;255;20;147");
+        assert_eq!(pink.sgr(ColorDepth::Ansi256, false), "38;5;198");
+        assert_eq!(pink.sgr(ColorDepth::Ansi16, false), "35");
+        assert_eq!(pink.sgr(ColorDepth::Ansi16, true), "45");
+
+        let pinned = Color {
+            ansi256: Some(199),
+            ansi16: Some(13),
+            ..pink
+        };
+        assert_eq!(pinned.sgr(ColorDepth::TrueColor, false), "38;2;255;20;147");
+        assert_eq!(pinned.sgr(ColorDepth::Ansi256, true), "48;5;199");
+        assert_eq!(pinned.sgr(ColorDepth::Ansi16, false), "95");
+        assert_eq!(pinned.sgr(ColorDepth::Ansi16, true), "105");
+    }
+
+    #[test]
+    fn test_detect_depth() {
+        assert_eq!(ColorDepth::detect(&fake_env(&[])), ColorDepth::TrueColor);
+        assert_eq!(
+            ColorDepth::detect(&fake_env(&[("COLORTERM", "truecolor"), ("TERM", "xterm")])),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(ColorDepth::detect(&fake_env(&[("TERM", "xterm-direct")])), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::detect(&fake_env(&[("TERM", "tmux-256color")])), ColorDepth::Ansi256);
+        assert_eq!(ColorDepth::detect(&fake_env(&[("TERM", "xterm")])), ColorDepth::Ansi16);
+    }
+
+    #[test]
+    fn test_parse_color_forms() {
+        #[derive(Debug, Deserialize)]
+        struct Colors {
+            plain: Color,
+            pinned: Color,
+        }
+
+        let colors: Colors = toml::from_str(
+            r##"
+            plain = "#ff1493"
+            pinned = { rgb = "#ff1493", ansi256 = 199, ansi16 = "bright_magenta" }
+            "##,
+        )
+        .unwrap();
+        assert_eq!(colors.plain, Color::from(Rgb::new(255, 20, 147)));
+        assert_eq!(colors.pinned.ansi256, Some(199));
+        assert_eq!(colors.pinned.ansi16, Some(13));
+
+        let numbered: Color = toml::from_str::<toml::Value>("c = { rgb = \"#000000\", ansi16 = 4 }").unwrap()["c"]
+            .clone()
+            .try_into()
+            .unwrap();
+        assert_eq!(numbered.ansi16, Some(4));
+
+        let error = toml::from_str::<Colors>("plain = \"pink\"\npinned = \"#000000\"").unwrap_err();
+        assert!(error.to_string().contains("invalid color `pink`"), "{}", error);
+        let error = toml::from_str::<Colors>("plain = \"#000000\"\npinned = { rgb = \"#000000\", ansi16 = 16 }")
+            .unwrap_err();
+        assert!(error.to_string().contains("invalid basic color"), "{}", error);
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let color = Rgb::new(64, 224, 208);
+        assert_eq!(color.to_string(), "#40e0d0");
+        assert_eq!(color.to_string().parse::<Rgb>(), Ok(color));
+    }
+}
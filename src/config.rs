@@ -0,0 +1,684 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::color::{Color, ColorDepth};
+use crate::pricing::ModelPricing;
+use crate::template::{self, Template};
+
+/// The segments a statusline can be built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentKind {
+    Directory,
+    Vcs,
+    Model,
+    OutputStyle,
+    Context,
+    Cost,
+}
+
+impl SegmentKind {
+    pub const ALL: [SegmentKind; 6] = [
+        SegmentKind::Directory,
+        SegmentKind::Vcs,
+        SegmentKind::Model,
+        SegmentKind::OutputStyle,
+        SegmentKind::Context,
+        SegmentKind::Cost,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SegmentKind::Directory => "directory",
+            SegmentKind::Vcs => "vcs",
+            SegmentKind::Model => "model",
+            SegmentKind::OutputStyle => "output_style",
+            SegmentKind::Context => "context",
+            SegmentKind::Cost => "cost",
+        }
+    }
+
+    /// When the line is too wide, lower priorities are shortened first and
+    /// dropped first
+    pub fn default_priority(&self) -> u8 {
+        match self {
+            SegmentKind::Directory => 50,
+            SegmentKind::Vcs => 40,
+            SegmentKind::Model => 30,
+            SegmentKind::Context => 20,
+            SegmentKind::Cost => 15,
+            SegmentKind::OutputStyle => 10,
+        }
+    }
+}
+
+/// Whether the line is colored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMode {
+    Always,
+    /// Colored unless the environment asks otherwise
+    #[default]
+    Auto,
+    Never,
+}
+
+impl ColorMode {
+    /// Whether to color, looking up environment variables through `var`.
+    /// `auto` follows `NO_COLOR`, then `CLICOLOR_FORCE`, then `TERM=dumb`.
+    /// It doesn't check for a terminal: Claude Code always reads the line
+    /// through a pipe.
+    pub fn resolve(self, var: &dyn Fn(&str) -> Option<String>) -> bool {
+        let is_set = |name| var(name).is_some_and(|value| !value.is_empty());
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto if is_set("NO_COLOR") => false,
+            ColorMode::Auto if is_set("CLICOLOR_FORCE") => var("CLICOLOR_FORCE").as_deref() != Some("0"),
+            ColorMode::Auto => var("TERM").as_deref() != Some("dumb"),
+        }
+    }
+}
+
+impl std::str::FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(ColorMode::Always),
+            "auto" => Ok(ColorMode::Auto),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(format!("invalid color mode `{}`: expected `always`, `auto` or `never`", s)),
+        }
+    }
+}
+
+/// Which icons and punctuation the line is drawn with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IconSet {
+    #[default]
+    Emoji,
+    /// Plain ASCII, for terminals without emoji fonts
+    Ascii,
+}
+
+/// What the directory segment shows the path relative to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectoryAnchor {
+    /// `~/src/monorepo/services/billing/api`
+    #[default]
+    Home,
+    /// `monorepo › services/billing/api`, from Claude Code's project
+    /// directory; falls back to `home` when there is none
+    Project,
+}
+
+/// Which end of its row a segment sits at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    #[default]
+    Left,
+    /// Pushed against the right edge; needs a known width
+    Right,
+}
+
+/// How segments are set apart
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeparatorStyle {
+    /// The `separator` string between uncolored backgrounds
+    #[default]
+    Plain,
+    /// Each segment on its own background, joined by arrows (``)
+    Powerline,
+    /// Each segment on its own background, with rounded caps (``)
+    Rounded,
+}
+
+/// How long a run may spend collecting slow segments (VCS status) before
+/// showing what it has
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(150);
+
+/// How long a cached VCS status is reused while the repository's metadata
+/// looks unchanged. Bounds how long edits to tracked files go unseen.
+const DEFAULT_VCS_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// User settings read from `config.toml` (or `config.json`). Every field is
+/// an override: anything left out keeps the built-in look.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Template for the whole line, e.g. `"{dir} {vcs?} {model} {style?}"`.
+    /// Replaces `order` and `separator` when set.
+    pub format: Option<String>,
+    /// Segments to show, left to right
+    pub order: Option<Vec<SegmentKind>>,
+    pub separator: Option<String>,
+    pub separator_color: Option<Color>,
+    /// `plain` (the default), `powerline` or `rounded`; the last two need a
+    /// Nerd Font or powerline-patched font, and only apply without `format`
+    pub separator_style: Option<SeparatorStyle>,
+    /// A built-in theme (`vibrant`, `solarized`, `gruvbox`, `catppuccin`,
+    /// `high-contrast`), a file in `themes/` next to this config, or a path
+    /// to a theme file
+    pub theme: Option<String>,
+    /// `always`, `auto` (the default, following `NO_COLOR` and
+    /// `CLICOLOR_FORCE`) or `never`
+    pub color: Option<ColorMode>,
+    /// `emoji` (the default) or `ascii`; icons set per segment still win
+    pub icons: Option<IconSet>,
+    /// `truecolor`, `256` or `16`; detected from `COLORTERM` and `TERM`
+    /// when left out
+    pub color_depth: Option<ColorDepth>,
+    /// Makes the directory and VCS segments clickable links (OSC 8) in
+    /// terminals that support them; off by default
+    pub hyperlinks: Option<bool>,
+    /// Time budget per run in milliseconds; slower VCS probes are killed
+    /// and their segment shows the last known status
+    pub timeout_ms: Option<u64>,
+    /// Columns the line may take; defaults to `COLUMNS`, and unlimited
+    /// without it
+    pub width: Option<usize>,
+    pub directory: SegmentConfig,
+    pub vcs: SegmentConfig,
+    pub model: SegmentConfig,
+    pub output_style: SegmentConfig,
+    pub context: SegmentConfig,
+    pub cost: SegmentConfig,
+    /// Per-model prices in USD per million tokens, keyed by model id prefix.
+    /// Overrides the built-in table for new models and negotiated rates.
+    pub pricing: BTreeMap<String, ModelPricing>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SegmentConfig {
+    pub enabled: Option<bool>,
+    pub icon: Option<String>,
+    pub color: Option<Color>,
+    /// Higher priorities are shortened and dropped last when the line
+    /// doesn't fit the width
+    pub priority: Option<u8>,
+    /// Line of a multi-line layout the segment goes on, counting from 1
+    pub row: Option<usize>,
+    /// `left` (the default) or `right`: right-aligned segments are padded
+    /// against the right edge when the width is known and they fit
+    pub align: Option<Align>,
+    /// Directory only: `home` (the default) or `project`
+    pub anchor: Option<DirectoryAnchor>,
+    /// Directory only: color for the project name with `anchor = "project"`
+    pub project_color: Option<Color>,
+    /// VCS only: icon shown when there are uncommitted changes
+    pub dirty_icon: Option<String>,
+    /// VCS only: color for bookmarks and branch names
+    pub ref_color: Option<Color>,
+    /// VCS only: where revisions and refs link to with `hyperlinks` on,
+    /// e.g. `https://forge/{repo}/commit/{id}`; `{repo}` is the name of the
+    /// repository's root directory and `{id}` the revision or ref
+    pub link_url: Option<String>,
+    /// VCS only: milliseconds to reuse a cached status while the repository
+    /// metadata is unchanged; 0 always probes
+    pub cache_ttl_ms: Option<u64>,
+    /// Context only: color once the context window is nearly full
+    pub warn_color: Option<Color>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io { path: PathBuf, source: std::io::Error },
+    Parse { path: PathBuf, message: String },
+    Invalid { path: PathBuf, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => {
+                write!(f, "cannot read config {}: {}", path.display(), source)
+            }
+            ConfigError::Parse { path, message } => {
+                write!(f, "invalid config {}: {}", path.display(), message.trim_end())
+            }
+            ConfigError::Invalid { path, message } => {
+                write!(f, "invalid config {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Loads the config from `path` if given, otherwise from the default
+    /// location when a file exists there
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        match path {
+            Some(path) => Self::from_file(path),
+            None => match default_config_path() {
+                Some(path) if path.exists() => Self::from_file(&path),
+                _ => Ok(Self::default()),
+            },
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let config: Config = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&text).map_err(|e| e.to_string())
+        }
+        .map_err(|message| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message,
+        })?;
+
+        config.validate().map_err(|message| ConfigError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        })?;
+
+        Ok(config)
+    }
+
+    /// Checks the rules serde can't express on its own
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(format) = &self.format {
+            let template = Template::parse(format).map_err(|e| format!("`format`: {}", e))?;
+            template
+                .check_names(template::VARIABLES)
+                .map_err(|e| format!("`format`: {}", e))?;
+        }
+
+        if self.timeout_ms == Some(0) {
+            return Err("`timeout_ms` must be greater than 0".to_string());
+        }
+
+        if self.width == Some(0) {
+            return Err("`width` must be greater than 0".to_string());
+        }
+        if let Some(kind) = SegmentKind::ALL.into_iter().find(|&kind| self.segment(kind).row == Some(0)) {
+            return Err(format!("`{}.row` must be 1 or more", kind.name()));
+        }
+
+        if let Some(order) = &self.order {
+            for (i, kind) in order.iter().enumerate() {
+                if order[..i].contains(kind) {
+                    return Err(format!("segment `{}` appears more than once in `order`", kind.name()));
+                }
+            }
+        }
+
+        for kind in SegmentKind::ALL {
+            let segment = self.segment(kind);
+            let specific_fields = [
+                ("anchor", segment.anchor.is_some(), SegmentKind::Directory),
+                ("project_color", segment.project_color.is_some(), SegmentKind::Directory),
+                ("dirty_icon", segment.dirty_icon.is_some(), SegmentKind::Vcs),
+                ("ref_color", segment.ref_color.is_some(), SegmentKind::Vcs),
+                ("cache_ttl_ms", segment.cache_ttl_ms.is_some(), SegmentKind::Vcs),
+                ("link_url", segment.link_url.is_some(), SegmentKind::Vcs),
+                ("warn_color", segment.warn_color.is_some(), SegmentKind::Context),
+            ];
+            for (field, is_set, owner) in specific_fields {
+                if is_set && kind != owner {
+                    return Err(format!(
+                        "`{}.{}` is only supported for `{}`",
+                        kind.name(),
+                        field,
+                        owner.name()
+                    ));
+                }
+            }
+        }
+
+        if let Some(link_url) = &self.vcs.link_url {
+            let mut rest = link_url.as_str();
+            while let Some(start) = rest.find('{') {
+                let end = rest[start..].find('}').map(|end| start + end + 1).unwrap_or(rest.len());
+                let placeholder = &rest[start..end];
+                if placeholder != "{repo}" && placeholder != "{id}" {
+                    return Err(format!(
+                        "`vcs.link_url`: unknown placeholder `{}`: expected `{{repo}}` or `{{id}}`",
+                        placeholder
+                    ));
+                }
+                rest = &rest[end..];
+            }
+        }
+
+        for (model, pricing) in &self.pricing {
+            let prices = [pricing.input, pricing.output, pricing.cache_write, pricing.cache_read];
+            if prices.iter().any(|price| !price.is_finite() || *price < 0.0) {
+                return Err(format!("`pricing.{}`: prices must be non-negative numbers", model));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout_ms.map(Duration::from_millis).unwrap_or(DEFAULT_TIMEOUT)
+    }
+
+    pub fn vcs_cache_ttl(&self) -> Duration {
+        self.vcs.cache_ttl_ms.map(Duration::from_millis).unwrap_or(DEFAULT_VCS_CACHE_TTL)
+    }
+
+    pub fn segment(&self, kind: SegmentKind) -> &SegmentConfig {
+        match kind {
+            SegmentKind::Directory => &self.directory,
+            SegmentKind::Vcs => &self.vcs,
+            SegmentKind::Model => &self.model,
+            SegmentKind::OutputStyle => &self.output_style,
+            SegmentKind::Context => &self.context,
+            SegmentKind::Cost => &self.cost,
+        }
+    }
+
+    pub fn priority(&self, kind: SegmentKind) -> u8 {
+        self.segment(kind).priority.unwrap_or_else(|| kind.default_priority())
+    }
+
+    /// Enabled segments in display order
+    pub fn segments(&self) -> Vec<SegmentKind> {
+        let order = self.order.as_deref().unwrap_or(&SegmentKind::ALL);
+        order
+            .iter()
+            .copied()
+            .filter(|&kind| self.segment(kind).enabled.unwrap_or(true))
+            .collect()
+    }
+
+    /// Enabled segments grouped by `row`, top to bottom; rows nothing is
+    /// on are skipped. Always at least one row, even if it's empty.
+    pub fn rows(&self) -> Vec<Vec<SegmentKind>> {
+        let mut rows: BTreeMap<usize, Vec<SegmentKind>> = BTreeMap::new();
+        for kind in self.segments() {
+            rows.entry(self.segment(kind).row.unwrap_or(1)).or_default().push(kind);
+        }
+        if rows.is_empty() {
+            return vec![Vec::new()];
+        }
+        rows.into_values().collect()
+    }
+}
+
+/// `$XDG_CONFIG_HOME/claude-statusline/config.toml`, falling back to
+/// `~/.config` when `XDG_CONFIG_HOME` is unset
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config_home.join("claude-statusline").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Rgb;
+    use crate::test_util::fake_env;
+
+    fn parse(text: &str) -> Result<Config, String> {
+        let config: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_empty_config_is_default() {
+        assert_eq!(parse("").unwrap(), Config::default());
+        assert_eq!(Config::default().segments(), SegmentKind::ALL.to_vec());
+    }
+
+    #[test]
+    fn test_order_and_disabled_segments() {
+        let config = parse(
+            r#"
+            order = ["model", "directory", "vcs"]
+
+            [vcs]
+            enabled = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.segments(), vec![SegmentKind::Model, SegmentKind::Directory]);
+    }
+
+    #[test]
+    fn test_segment_overrides() {
+        let config = parse(
+            r##"
+            separator = " | "
+
+            [directory]
+            icon = "D"
+            color = "#ffffff"
+
+            [vcs]
+            dirty_icon = "!"
+            ref_color = "#00ff00"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.separator.as_deref(), Some(" | "));
+        assert_eq!(config.directory.icon.as_deref(), Some("D"));
+        assert_eq!(config.directory.color, Some(Rgb::new(255, 255, 255).into()));
+        assert_eq!(config.vcs.dirty_icon.as_deref(), Some("!"));
+    }
+
+    #[test]
+    fn test_unknown_segment_is_rejected() {
+        let error = parse(r#"order = ["directory", "weather"]"#).unwrap_err();
+        assert!(error.contains("unknown variant `weather`"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        let error = parse("[model]\ncolour = \"#ffffff\"").unwrap_err();
+        assert!(error.contains("unknown field `colour`"), "{}", error);
+    }
+
+    #[test]
+    fn test_bad_color_is_rejected() {
+        let error = parse("[model]\ncolor = \"orange\"").unwrap_err();
+        assert!(error.contains("invalid color `orange`"), "{}", error);
+    }
+
+    #[test]
+    fn test_duplicate_segment_is_rejected() {
+        let error = parse(r#"order = ["model", "model"]"#).unwrap_err();
+        assert_eq!(error, "segment `model` appears more than once in `order`");
+    }
+
+    #[test]
+    fn test_vcs_only_fields_are_rejected_elsewhere() {
+        let error = parse("[model]\nref_color = \"#ffffff\"").unwrap_err();
+        assert_eq!(error, "`model.ref_color` is only supported for `vcs`");
+
+        let error = parse("[vcs]\nwarn_color = \"#ffffff\"").unwrap_err();
+        assert_eq!(error, "`vcs.warn_color` is only supported for `context`");
+    }
+
+    #[test]
+    fn test_format_template_is_validated() {
+        assert!(parse(r#"format = "{dir} {vcs?} {model}""#).is_ok());
+
+        let error = parse(r#"format = "{dir} {vcs""#).unwrap_err();
+        assert_eq!(error, "`format`: template error at column 7: unclosed `{`");
+
+        let error = parse(r#"format = "{dir} {weather}""#).unwrap_err();
+        assert!(error.starts_with("`format`: unknown placeholder `{weather}`"), "{}", error);
+    }
+
+    #[test]
+    fn test_timeout() {
+        assert_eq!(Config::default().timeout(), Duration::from_millis(150));
+        assert_eq!(parse("timeout_ms = 500").unwrap().timeout(), Duration::from_millis(500));
+        assert_eq!(parse("timeout_ms = 0").unwrap_err(), "`timeout_ms` must be greater than 0");
+    }
+
+    #[test]
+    fn test_width_and_priorities() {
+        let config = parse("width = 80\n[cost]\npriority = 90").unwrap();
+        assert_eq!(config.width, Some(80));
+        assert_eq!(config.priority(SegmentKind::Cost), 90);
+        assert_eq!(config.priority(SegmentKind::Directory), 50);
+        assert_eq!(parse("width = 0").unwrap_err(), "`width` must be greater than 0");
+    }
+
+    #[test]
+    fn test_color_and_icons() {
+        let config = parse("color = \"never\"\nicons = \"ascii\"").unwrap();
+        assert_eq!(config.color, Some(ColorMode::Never));
+        assert_eq!(config.icons, Some(IconSet::Ascii));
+
+        let config = parse("separator_style = \"rounded\"").unwrap();
+        assert_eq!(config.separator_style, Some(SeparatorStyle::Rounded));
+
+        let error = parse("color = \"sometimes\"").unwrap_err();
+        assert!(error.contains("unknown variant `sometimes`"), "{}", error);
+    }
+
+    #[test]
+    fn test_color_depth_and_pinned_colors() {
+        let config = parse(
+            r##"
+            color_depth = "256"
+
+            [vcs]
+            ref_color = { rgb = "#ff1493", ansi256 = 199, ansi16 = "bright_magenta" }
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.color_depth, Some(ColorDepth::Ansi256));
+        assert_eq!(config.vcs.ref_color.unwrap().ansi256, Some(199));
+
+        let error = parse("color_depth = \"8\"").unwrap_err();
+        assert!(error.contains("unknown variant `8`"), "{}", error);
+    }
+
+    #[test]
+    fn test_color_mode_follows_environment() {
+        assert!(ColorMode::Auto.resolve(&fake_env(&[])));
+        assert!(!ColorMode::Auto.resolve(&fake_env(&[("NO_COLOR", "1")])));
+        assert!(ColorMode::Auto.resolve(&fake_env(&[("NO_COLOR", "")]))); // Empty means unset
+        assert!(!ColorMode::Auto.resolve(&fake_env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")])));
+        assert!(ColorMode::Auto.resolve(&fake_env(&[("CLICOLOR_FORCE", "1"), ("TERM", "dumb")])));
+        assert!(!ColorMode::Auto.resolve(&fake_env(&[("CLICOLOR_FORCE", "0"), ("TERM", "dumb")])));
+        assert!(!ColorMode::Auto.resolve(&fake_env(&[("TERM", "dumb")])));
+        assert!(ColorMode::Always.resolve(&fake_env(&[("NO_COLOR", "1")])));
+        assert!(!ColorMode::Never.resolve(&fake_env(&[("CLICOLOR_FORCE", "1")])));
+    }
+
+    #[test]
+    fn test_vcs_cache_ttl() {
+        assert_eq!(Config::default().vcs_cache_ttl(), Duration::from_secs(5));
+        assert_eq!(parse("[vcs]\ncache_ttl_ms = 0").unwrap().vcs_cache_ttl(), Duration::ZERO);
+        assert_eq!(
+            parse("[model]\ncache_ttl_ms = 10").unwrap_err(),
+            "`model.cache_ttl_ms` is only supported for `vcs`"
+        );
+    }
+
+    #[test]
+    fn test_rows() {
+        use SegmentKind::*;
+        assert_eq!(Config::default().rows(), vec![SegmentKind::ALL.to_vec()]);
+
+        let config = parse("[model]\nrow = 2\n[context]\nrow = 2\n[cost]\nrow = 5\n[output_style]\nenabled = false").unwrap();
+        assert_eq!(config.rows(), vec![vec![Directory, Vcs], vec![Model, Context], vec![Cost]]);
+
+        let config = parse("order = []").unwrap();
+        assert_eq!(config.rows(), vec![Vec::new()]);
+
+        assert_eq!(parse("[vcs]\nrow = 0").unwrap_err(), "`vcs.row` must be 1 or more");
+    }
+
+    #[test]
+    fn test_directory_anchor() {
+        let config = parse("[directory]\nanchor = \"project\"\nproject_color = \"#ffffff\"").unwrap();
+        assert_eq!(config.directory.anchor, Some(DirectoryAnchor::Project));
+        assert_eq!(
+            parse("[vcs]\nanchor = \"project\"").unwrap_err(),
+            "`vcs.anchor` is only supported for `directory`"
+        );
+    }
+
+    #[test]
+    fn test_align() {
+        let config = parse("[cost]\nalign = \"right\"").unwrap();
+        assert_eq!(config.cost.align, Some(Align::Right));
+        assert!(parse("[cost]\nalign = \"center\"").unwrap_err().contains("unknown variant `center`"));
+    }
+
+    #[test]
+    fn test_link_url_placeholders() {
+        let config = parse("hyperlinks = true\n[vcs]\nlink_url = \"https://forge/{repo}/commit/{id}\"").unwrap();
+        assert_eq!(config.hyperlinks, Some(true));
+        assert_eq!(
+            parse("[vcs]\nlink_url = \"https://forge/{project}/commit/{id}\"").unwrap_err(),
+            "`vcs.link_url`: unknown placeholder `{project}`: expected `{repo}` or `{id}`"
+        );
+        assert_eq!(
+            parse("[directory]\nlink_url = \"https://forge\"").unwrap_err(),
+            "`directory.link_url` is only supported for `vcs`"
+        );
+    }
+
+    #[test]
+    fn test_pricing_overrides() {
+        let config = parse(
+            r#"
+            [pricing."claude-sonnet-4"]
+            input = 2.5
+            output = 12.0
+            cache_write = 3.0
+            cache_read = 0.25
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.pricing["claude-sonnet-4"].output, 12.0);
+
+        let error = parse("[pricing.custom]\ninput = 1.0\noutput = 1.0").unwrap_err();
+        assert!(error.contains("missing field `cache_write`"), "{}", error);
+
+        let error = parse(
+            "[pricing.custom]\ninput = -1.0\noutput = 1.0\ncache_write = 1.0\ncache_read = 1.0",
+        )
+        .unwrap_err();
+        assert_eq!(error, "`pricing.custom`: prices must be non-negative numbers");
+    }
+
+    #[test]
+    fn test_load_json_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, r#"{"order": ["model"], "model": {"icon": "M"}}"#).unwrap();
+
+        let config = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.segments(), vec![SegmentKind::Model]);
+        assert_eq!(config.model.icon.as_deref(), Some("M"));
+    }
+
+    #[test]
+    fn test_load_missing_explicit_file_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = Config::load(Some(&dir.path().join("nope.toml"))).unwrap_err();
+        assert!(error.to_string().starts_with("cannot read config"));
+    }
+}
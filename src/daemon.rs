@@ -0,0 +1,373 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::deadline::Cancel;
+use crate::vcs::{default_providers, select_provider, VcsProvider, VcsStatus};
+use crate::watch::Watcher;
+
+/// How long one background refresh may take; the daemon has no statusline
+/// waiting on it, so this is generous
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Entries older than this are refreshed on the next request even without a
+/// change notification, for anything the watcher can't see
+const MAX_AGE: Duration = Duration::from_secs(30);
+/// Lets a burst of events (a save, a checkout) settle before refreshing
+const SETTLE_DELAY: Duration = Duration::from_millis(50);
+/// Directories nobody asked about for this long are forgotten and unwatched
+const IDLE_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// `daemon.sock` in the cache dir
+pub fn socket_path(cache_dir: &Path) -> PathBuf {
+    cache_dir.join("daemon.sock")
+}
+
+/// One JSON line from the statusline
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    path: String,
+}
+
+/// One JSON line back. `status` is `None` outside a repository.
+#[derive(Debug, Serialize, Deserialize)]
+struct Response {
+    status: Option<VcsStatus>,
+    /// A change was seen (or the entry aged out) and a refresh is running
+    stale: bool,
+}
+
+/// Asks a running daemon for the VCS status of `path`. Returns `None` when
+/// no daemon answered before `deadline`, and the caller should collect the
+/// status itself.
+pub fn query(socket: &Path, path: &str, deadline: Instant) -> Option<Option<VcsStatus>> {
+    let timeout = deadline.checked_duration_since(Instant::now()).filter(|left| !left.is_zero())?;
+    let mut stream = UnixStream::connect(socket).ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+
+    let mut request = serde_json::to_vec(&Request { path: path.to_string() }).ok()?;
+    request.push(b'\n');
+    stream.write_all(&request).ok()?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).ok()?;
+    let response: Response = serde_json::from_str(&line).ok()?;
+    Some(response.status.map(|status| VcsStatus {
+        stale: response.stale,
+        ..status
+    }))
+}
+
+struct Entry {
+    root: Option<PathBuf>,
+    status: Option<VcsStatus>,
+    refreshed: Instant,
+    last_request: Instant,
+    /// Bumped on every change notification, so a refresh that started
+    /// before a change doesn't mark the entry fresh
+    generation: u64,
+    refreshed_generation: u64,
+    refreshing: bool,
+}
+
+impl Entry {
+    fn is_stale(&self) -> bool {
+        self.generation != self.refreshed_generation || self.refreshed.elapsed() > MAX_AGE
+    }
+}
+
+/// Keeps the VCS status of every directory the statusline asks about,
+/// refreshed in the background when the watcher sees the repository change,
+/// and answers with the cached value right away (stale-while-revalidate)
+pub struct Daemon {
+    providers: Vec<Arc<dyn VcsProvider>>,
+    entries: Mutex<HashMap<PathBuf, Entry>>,
+    watcher: Watcher,
+}
+
+impl Daemon {
+    pub fn new(providers: Vec<Arc<dyn VcsProvider>>) -> io::Result<Self> {
+        Ok(Self {
+            providers,
+            entries: Mutex::new(HashMap::new()),
+            watcher: Watcher::new()?,
+        })
+    }
+
+    /// Serves requests on `listener` until it fails
+    pub fn serve(self: Arc<Self>, listener: UnixListener) -> io::Result<()> {
+        let watching = Arc::clone(&self);
+        thread::spawn(move || watching.watcher.run(&|root| watching.changed(root)));
+
+        let sweeping = Arc::clone(&self);
+        thread::spawn(move || loop {
+            thread::sleep(MAX_AGE);
+            sweeping.expire_idle();
+        });
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let daemon = Arc::clone(&self);
+            thread::spawn(move || {
+                if let Err(e) = daemon.answer(stream) {
+                    eprintln!("daemon: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn answer(self: &Arc<Self>, stream: UnixStream) -> io::Result<()> {
+        let mut line = String::new();
+        if BufReader::new(&stream).read_line(&mut line)? == 0 {
+            // A liveness probe from `run`
+            return Ok(());
+        }
+        let request: Request = serde_json::from_str(&line)?;
+
+        let response = self.lookup(Path::new(&request.path));
+        let mut response = serde_json::to_vec(&response)?;
+        response.push(b'\n');
+        (&stream).write_all(&response)
+    }
+
+    fn lookup(self: &Arc<Self>, path: &Path) -> Response {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(path) {
+            entry.last_request = Instant::now();
+            let stale = entry.is_stale();
+            if stale && !entry.refreshing {
+                entry.refreshing = true;
+                self.spawn_refresh(path.to_path_buf());
+            }
+            return Response {
+                status: entry.status.clone(),
+                stale,
+            };
+        }
+
+        // First request for this directory: the statusline is waiting, so
+        // collect right here, then keep it fresh from now on
+        let (root, status) = self.collect(path);
+        if let Some(root) = &root {
+            let already_watched = self
+                .entries
+                .lock()
+                .unwrap()
+                .values()
+                .any(|entry| entry.root.as_ref() == Some(root));
+            if !already_watched {
+                self.watcher.watch(root);
+            }
+        }
+
+        let now = Instant::now();
+        self.entries.lock().unwrap().insert(
+            path.to_path_buf(),
+            Entry {
+                root,
+                status: status.clone(),
+                refreshed: now,
+                last_request: now,
+                generation: 0,
+                refreshed_generation: 0,
+                refreshing: false,
+            },
+        );
+        Response { status, stale: false }
+    }
+
+    fn collect(&self, path: &Path) -> (Option<PathBuf>, Option<VcsStatus>) {
+        match select_provider(&self.providers, path) {
+            Some((provider, root)) => {
+                let status = provider.status(path, Instant::now() + REFRESH_TIMEOUT, &Cancel::default());
+                let status = status.map(|status| status.with_root(&root));
+                (Some(root), status)
+            }
+            None => (None, None),
+        }
+    }
+
+    /// Called by the watcher for every event in the repository at `root`
+    fn changed(self: &Arc<Self>, root: &Path) {
+        let mut entries = self.entries.lock().unwrap();
+        for (path, entry) in entries.iter_mut() {
+            if entry.root.as_deref() != Some(root) {
+                continue;
+            }
+            entry.generation += 1;
+            if !entry.refreshing {
+                entry.refreshing = true;
+                self.spawn_refresh(path.clone());
+            }
+        }
+    }
+
+    fn spawn_refresh(self: &Arc<Self>, path: PathBuf) {
+        let daemon = Arc::clone(self);
+        thread::spawn(move || daemon.refresh(path));
+    }
+
+    fn refresh(self: &Arc<Self>, path: PathBuf) {
+        loop {
+            thread::sleep(SETTLE_DELAY);
+            let generation = match self.entries.lock().unwrap().get(&path) {
+                Some(entry) => entry.generation,
+                None => return,
+            };
+
+            let (_, status) = self.collect(&path);
+
+            let mut entries = self.entries.lock().unwrap();
+            let Some(entry) = entries.get_mut(&path) else {
+                return;
+            };
+            entry.status = status;
+            entry.refreshed = Instant::now();
+            entry.refreshed_generation = generation;
+
+            // Changes arrived while collecting: go again
+            if entry.generation == generation {
+                entry.refreshing = false;
+                return;
+            }
+        }
+    }
+
+    fn expire_idle(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        let mut expired_roots = Vec::new();
+        entries.retain(|_, entry| {
+            let keep = entry.last_request.elapsed() < IDLE_EXPIRY;
+            if !keep {
+                expired_roots.extend(entry.root.clone());
+            }
+            keep
+        });
+
+        for root in expired_roots {
+            if !entries.values().any(|entry| entry.root.as_ref() == Some(&root)) {
+                self.watcher.unwatch(&root);
+            }
+        }
+    }
+}
+
+/// Runs the daemon on `socket` until killed. A socket left behind by a
+/// daemon that died is replaced; one with a live daemon behind it is an
+/// error.
+pub fn run(socket: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if UnixStream::connect(socket).is_ok() {
+        return Err(format!("a daemon is already listening on {}", socket.display()).into());
+    }
+    if let Some(parent) = socket.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let _ = fs::remove_file(socket);
+
+    let listener = UnixListener::bind(socket)?;
+    Arc::new(Daemon::new(default_providers())?).serve(listener)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Reports a revision that counts how often status was collected
+    struct CountingProvider {
+        root: PathBuf,
+        calls: AtomicUsize,
+    }
+
+    impl VcsProvider for CountingProvider {
+        fn name(&self) -> &'static str {
+            "jj"
+        }
+
+        fn detect(&self, path: &Path) -> Option<PathBuf> {
+            path.starts_with(&self.root).then(|| self.root.clone())
+        }
+
+        fn status(&self, _path: &Path, _deadline: Instant, _cancel: &Cancel) -> Option<VcsStatus> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            Some(VcsStatus::new("jj", &calls.to_string()))
+        }
+    }
+
+    fn start(root: &Path) -> (tempfile::TempDir, PathBuf, Arc<Daemon>) {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = socket_path(dir.path());
+        let provider = CountingProvider {
+            root: root.to_path_buf(),
+            calls: AtomicUsize::new(0),
+        };
+        let daemon = Arc::new(Daemon::new(vec![Arc::new(provider)]).unwrap());
+        let listener = UnixListener::bind(&socket).unwrap();
+        let serving = Arc::clone(&daemon);
+        thread::spawn(move || serving.serve(listener));
+        (dir, socket, daemon)
+    }
+
+    fn ask(socket: &Path, path: &Path) -> Option<Option<VcsStatus>> {
+        query(socket, path.to_str().unwrap(), Instant::now() + Duration::from_secs(5))
+    }
+
+    #[test]
+    fn test_query_without_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(ask(&socket_path(dir.path()), dir.path()), None);
+    }
+
+    #[test]
+    fn test_cached_until_changed_then_revalidated() {
+        let repo = tempfile::tempdir().unwrap();
+        let (_dir, socket, daemon) = start(repo.path());
+
+        let first = ask(&socket, repo.path()).unwrap().unwrap();
+        assert_eq!((first.revision.as_str(), first.stale), ("1", false));
+
+        // Answered from the cache, without collecting again
+        let cached = ask(&socket, repo.path()).unwrap().unwrap();
+        assert_eq!((cached.revision.as_str(), cached.stale), ("1", false));
+
+        // A change is refreshed in the background; until then the old value
+        // is served, marked stale
+        daemon.entries.lock().unwrap().get_mut(repo.path()).unwrap().generation += 1;
+        let stale = ask(&socket, repo.path()).unwrap().unwrap();
+        assert_eq!((stale.revision.as_str(), stale.stale), ("1", true));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let status = ask(&socket, repo.path()).unwrap().unwrap();
+            if !status.stale {
+                assert_eq!(status.revision, "2");
+                break;
+            }
+            assert!(Instant::now() < deadline, "refresh never finished");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_outside_repository() {
+        let repo = tempfile::tempdir().unwrap();
+        let elsewhere = tempfile::tempdir().unwrap();
+        let (_dir, socket, _daemon) = start(repo.path());
+        assert_eq!(ask(&socket, elsewhere.path()), Some(None));
+    }
+
+    #[test]
+    fn test_run_refuses_second_daemon() {
+        let repo = tempfile::tempdir().unwrap();
+        let (_dir, socket, _daemon) = start(repo.path());
+        let error = run(&socket).unwrap_err();
+        assert!(error.to_string().starts_with("a daemon is already listening"), "{}", error);
+    }
+}
@@ -0,0 +1,258 @@
+use std::io::{self, Read};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a running child process is checked against its deadline
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+/// Work started on its own thread by [`spawn`], to be collected with
+/// [`Task::wait`]
+pub struct Task<T> {
+    receiver: mpsc::Receiver<T>,
+}
+
+/// Starts `work` on its own thread right away, so several pieces of work can
+/// run at the same time and be waited for against one deadline
+pub fn spawn<T, F>(work: F) -> Task<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // The receiver is gone when the deadline passed first
+        let _ = sender.send(work());
+    });
+    Task { receiver }
+}
+
+impl<T> Task<T> {
+    /// The result, or `None` when the work didn't finish by `deadline`; the
+    /// thread is then left to finish (or die with the process) on its own,
+    /// which is what lets in-process work like a jj-lib snapshot be abandoned
+    pub fn wait(self, deadline: Instant) -> Option<T> {
+        self.receiver
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            .ok()
+    }
+
+    /// The result however long it takes, for work that's bounded by a
+    /// deadline of its own and cleans up after itself when it runs out
+    pub fn join(self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Stops work someone has given up waiting for. The child processes the
+/// work started through [`output_until`] belong to the [`Cancel`], so the
+/// waiting side can kill and reap them itself instead of trusting a thread
+/// it no longer waits for; work done in-process checks
+/// [`Cancel::is_cancelled`] between steps.
+#[derive(Debug, Clone, Default)]
+pub struct Cancel {
+    state: Arc<Mutex<CancelState>>,
+}
+
+#[derive(Debug, Default)]
+struct CancelState {
+    running: Vec<Child>,
+    cancelled: bool,
+}
+
+impl Cancel {
+    /// Kills and reaps every child still running; none start afterwards
+    pub fn cancel(&self) {
+        let mut state = self.lock();
+        state.cancelled = true;
+        for mut child in state.running.drain(..) {
+            kill(&mut child);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.lock().cancelled
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CancelState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Runs `work` on its own thread and waits for it until `deadline`. When it
+/// doesn't finish in time, whatever it started is cancelled before this
+/// returns, so no `git` or `jj` outlives the wait.
+pub fn run_until<T, F>(deadline: Instant, work: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Cancel) -> T + Send + 'static,
+{
+    let cancel = Cancel::default();
+    let result = {
+        let cancel = cancel.clone();
+        spawn(move || work(&cancel)).wait(deadline)
+    };
+    if result.is_none() {
+        cancel.cancel();
+    }
+    result
+}
+
+/// Like [`Command::output`], but kills the child if it's still running at
+/// `deadline` and fails with [`io::ErrorKind::TimedOut`]. The child is held
+/// by `cancel`, which may kill it first; that fails the same way.
+pub fn output_until(command: &mut Command, deadline: Instant, cancel: &Cancel) -> io::Result<Output> {
+    // Spawned under the lock, so a cancel can't slip in between starting the
+    // child and handing it over
+    let mut state = cancel.lock();
+    if state.cancelled {
+        return Err(timed_out());
+    }
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Drain both pipes while waiting, so a chatty child can't block on a full
+    // pipe and look hung
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    // Not reaped until it leaves `running`, so the id can't be reused meanwhile
+    let id = child.id();
+    state.running.push(child);
+    drop(state);
+
+    loop {
+        {
+            let mut state = cancel.lock();
+            let Some(index) = state.running.iter().position(|child| child.id() == id) else {
+                return Err(timed_out());
+            };
+            if let Some(status) = state.running[index].try_wait()? {
+                // Already reaped by `try_wait`
+                state.running.retain(|child| child.id() != id);
+                drop(state);
+                return Ok(Output {
+                    status,
+                    stdout: stdout.join().unwrap_or_default(),
+                    stderr: stderr.join().unwrap_or_default(),
+                });
+            }
+            if Instant::now() >= deadline {
+                kill(&mut state.running.swap_remove(index));
+                return Err(timed_out());
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "timed out")
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_until_returns_finished_work() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(run_until(deadline, |_| 42), Some(42));
+    }
+
+    #[test]
+    fn test_run_until_gives_up_at_deadline() {
+        let started = Instant::now();
+        let result = run_until(started + Duration::from_millis(20), |_| {
+            thread::sleep(Duration::from_secs(5));
+        });
+        assert_eq!(result, None);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_tasks_run_at_the_same_time() {
+        let started = Instant::now();
+        let tasks: Vec<_> = (0..4)
+            .map(|i| {
+                spawn(move || {
+                    thread::sleep(Duration::from_millis(100));
+                    i
+                })
+            })
+            .collect();
+        let deadline = started + Duration::from_secs(5);
+        let results: Vec<_> = tasks.into_iter().map(|task| task.wait(deadline)).collect();
+        assert_eq!(results, vec![Some(0), Some(1), Some(2), Some(3)]);
+        assert!(started.elapsed() < Duration::from_millis(350));
+    }
+
+    #[test]
+    fn test_output_until_collects_output() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo out; echo err >&2"]);
+        let output = output_until(&mut command, deadline, &Cancel::default()).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn test_output_until_kills_slow_child() {
+        let started = Instant::now();
+        let deadline = started + Duration::from_millis(20);
+        let error = output_until(Command::new("sleep").arg("5"), deadline, &Cancel::default()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_given_up_work_has_its_children_killed() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let script = format!("echo $$ > {}; exec sleep 30", pid_file.display());
+
+        // The child's own deadline is far off, so only the cancel stops it
+        let started = Instant::now();
+        let result = run_until(started + Duration::from_millis(200), move |cancel| {
+            let far_off = Instant::now() + Duration::from_secs(60);
+            output_until(Command::new("sh").args(["-c", &script]), far_off, cancel)
+        });
+        assert!(result.is_none());
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // Killed and reaped by the time `run_until` returned
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        assert!(!std::path::Path::new("/proc").join(pid.trim()).exists());
+    }
+
+    #[test]
+    fn test_no_children_start_once_cancelled() {
+        let cancel = Cancel::default();
+        cancel.cancel();
+        assert!(cancel.is_cancelled());
+        let error = output_until(&mut Command::new("true"), Instant::now() + Duration::from_secs(5), &cancel).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt::Display;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cache::default_cache_dir;
+
+/// The log is rotated to `debug.log.old` once it grows past this
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// `debug.log` in the cache dir. The statusline has nowhere else to report
+/// errors: Claude Code only shows stdout, and only on success.
+pub fn default_log_path() -> Option<PathBuf> {
+    default_cache_dir().map(|dir| dir.join("debug.log"))
+}
+
+/// Appends a timestamped line to the log. Failing to log must never cost the
+/// statusline, so callers are expected to ignore the result.
+pub fn log_error(path: &Path, context: &str, error: &dyn Display) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::metadata(path).is_ok_and(|metadata| metadata.len() > MAX_LOG_BYTES) {
+        fs::rename(path, path.with_extension("log.old"))?;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{} {}: {}", timestamp, context, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_appends_and_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs").join("debug.log");
+
+        log_error(&path, "bad input", &"missing field `workspace`").unwrap();
+        log_error(&path, "bad config", &"unknown field `colour`").unwrap();
+        let log = fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" bad input: missing field `workspace`"), "{}", lines[0]);
+
+        fs::write(&path, vec![b'x'; MAX_LOG_BYTES as usize + 1]).unwrap();
+        log_error(&path, "bad input", &"eof").unwrap();
+        assert!(path.with_extension("log.old").exists());
+        assert!(fs::read_to_string(&path).unwrap().ends_with("bad input: eof\n"));
+    }
+}
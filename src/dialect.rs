@@ -0,0 +1,214 @@
+use std::str::FromStr;
+
+/// Where the line is printed, which decides how colors are written and
+/// which characters need escaping. Lines are rendered with ANSI escape
+/// codes first and then translated, so every dialect shows the same
+/// segments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// Raw ANSI escape codes, for Claude Code and terminals
+    #[default]
+    Ansi,
+    /// tmux style directives like `#[fg=#40e0d0]`, for `status-left` and
+    /// `status-right`
+    Tmux,
+    /// Escapes wrapped in `%{ %}`, for zsh prompts
+    Zsh,
+    /// Escapes wrapped in `\[ \]`, for bash's `PS1`
+    Bash,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ansi" => Ok(Dialect::Ansi),
+            "tmux" => Ok(Dialect::Tmux),
+            "zsh" => Ok(Dialect::Zsh),
+            "bash" => Ok(Dialect::Bash),
+            _ => Err(format!("invalid format `{}`: expected `ansi`, `tmux`, `zsh` or `bash`", s)),
+        }
+    }
+}
+
+impl Dialect {
+    /// Whether the line is drawn for Claude Code, which sends its session
+    /// as JSON on stdin; prompts and tmux send nothing
+    pub fn reads_input(self) -> bool {
+        self == Dialect::Ansi
+    }
+
+    /// Rewrites a line rendered with ANSI escape codes for this dialect
+    pub fn translate(self, line: &str) -> String {
+        if self == Dialect::Ansi {
+            return line.to_string();
+        }
+
+        let mut translated = String::new();
+        for piece in pieces(line) {
+            match piece {
+                Piece::Text(text) => translated.push_str(&self.escape(text)),
+                Piece::Escape(escape) => match self {
+                    Dialect::Ansi => translated.push_str(escape),
+                    Dialect::Tmux => translated.push_str(&tmux_style(escape)),
+                    Dialect::Zsh => translated.push_str(&format!("%{{{}%}}", escape)),
+                    Dialect::Bash => translated.push_str(&format!("\\[{}\\]", escape)),
+                },
+            }
+        }
+        translated
+    }
+
+    /// Text as it must be written to show up literally
+    fn escape(self, text: &str) -> String {
+        match self {
+            Dialect::Ansi => text.to_string(),
+            Dialect::Tmux => text.replace('#', "##"),
+            Dialect::Zsh => text.replace('%', "%%"),
+            // PS1 is decoded and then expanded like a double-quoted
+            // string, so a bookmark named `$(rm -rf ~)` must not reach the
+            // expansion unquoted
+            Dialect::Bash => text.replace('\\', "\\\\\\\\").replace('$', "\\\\$").replace('`', "\\`"),
+        }
+    }
+}
+
+enum Piece<'a> {
+    Text(&'a str),
+    /// A whole escape sequence: CSI (`ESC [ ... m`) or OSC (`ESC ] ... BEL`
+    /// or `ESC ] ... ESC \`)
+    Escape(&'a str),
+}
+
+fn pieces(line: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('\x1b') {
+        if start > 0 {
+            pieces.push(Piece::Text(&rest[..start]));
+        }
+        let sequence = &rest[start..];
+        let end = match sequence.as_bytes().get(1) {
+            Some(b'[') => sequence[2..]
+                .find(|c| ('\x40'..='\x7e').contains(&c))
+                .map(|end| end + 3),
+            Some(b']') => {
+                let bel = sequence.find('\x07').map(|end| end + 1);
+                let st = sequence.find("\x1b\\").map(|end| end + 2);
+                match (bel, st) {
+                    (Some(bel), Some(st)) => Some(bel.min(st)),
+                    (bel, st) => bel.or(st),
+                }
+            }
+            _ => None,
+        }
+        .unwrap_or(sequence.len());
+        pieces.push(Piece::Escape(&sequence[..end]));
+        rest = &sequence[end..];
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest));
+    }
+    pieces
+}
+
+/// An SGR sequence as a tmux style directive. Other sequences (hyperlinks)
+/// have no tmux form and are dropped.
+fn tmux_style(escape: &str) -> String {
+    let Some(parameters) = escape.strip_prefix("\x1b[").and_then(|escape| escape.strip_suffix('m')) else {
+        return String::new();
+    };
+
+    let mut styles = Vec::new();
+    let mut codes = parameters.split(';').map(|code| code.parse::<u16>().unwrap_or(0));
+    while let Some(code) = codes.next() {
+        let style = match code {
+            0 => "default".to_string(),
+            1 => "bold".to_string(),
+            2 => "dim".to_string(),
+            3 => "italics".to_string(),
+            4 => "underscore".to_string(),
+            30..=37 => format!("fg=colour{}", code - 30),
+            90..=97 => format!("fg=colour{}", code - 90 + 8),
+            40..=47 => format!("bg=colour{}", code - 40),
+            100..=107 => format!("bg=colour{}", code - 100 + 8),
+            38 | 48 => {
+                let layer = if code == 38 { "fg" } else { "bg" };
+                match codes.next() {
+                    Some(5) => format!("{}=colour{}", layer, codes.next().unwrap_or(0)),
+                    Some(2) => {
+                        let mut channel = || codes.next().unwrap_or(0);
+                        format!("{}=#{:02x}{:02x}{:02x}", layer, channel(), channel(), channel())
+                    }
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+        styles.push(style);
+    }
+
+    if styles.is_empty() {
+        String::new()
+    } else {
+        format!("#[{}]", styles.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = "📂 \x1b[38;2;64;224;208m~/src/100%\x1b[0m\x1b[1;38;5;198m#main\x1b[0m";
+
+    #[test]
+    fn test_parse_dialect() {
+        assert_eq!("tmux".parse(), Ok(Dialect::Tmux));
+        assert_eq!(
+            "fish".parse::<Dialect>(),
+            Err("invalid format `fish`: expected `ansi`, `tmux`, `zsh` or `bash`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_ansi_is_unchanged() {
+        assert_eq!(Dialect::Ansi.translate(LINE), LINE);
+    }
+
+    #[test]
+    fn test_tmux_styles() {
+        assert_eq!(
+            Dialect::Tmux.translate(LINE),
+            "📂 #[fg=#40e0d0]~/src/100%#[default]#[bold,fg=colour198]##main#[default]"
+        );
+        assert_eq!(
+            Dialect::Tmux.translate("\x1b[30;46m a \x1b[36;101m>\x1b[0m"),
+            "#[fg=colour0,bg=colour6] a #[fg=colour6,bg=colour9]>#[default]"
+        );
+        // No tmux form for hyperlinks
+        assert_eq!(Dialect::Tmux.translate("\x1b]8;;file:///tmp\x1b\\tmp\x1b]8;;\x1b\\"), "tmp");
+    }
+
+    #[test]
+    fn test_zsh_wraps_escapes() {
+        assert_eq!(
+            Dialect::Zsh.translate(LINE),
+            "📂 %{\x1b[38;2;64;224;208m%}~/src/100%%%{\x1b[0m%}%{\x1b[1;38;5;198m%}#main%{\x1b[0m%}"
+        );
+    }
+
+    #[test]
+    fn test_bash_wraps_escapes_and_quotes_text() {
+        assert_eq!(
+            Dialect::Bash.translate("\x1b[1mmain\x1b[0m"),
+            "\\[\x1b[1m\\]main\\[\x1b[0m\\]"
+        );
+        assert_eq!(Dialect::Bash.translate("$(x) `y` \\z"), "\\\\$(x) \\`y\\` \\\\\\\\z");
+    }
+
+    #[test]
+    fn test_unterminated_escape() {
+        assert_eq!(Dialect::Zsh.translate("a\x1b[38"), "a%{\x1b[38%}");
+    }
+}
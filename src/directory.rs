@@ -29,6 +29,55 @@ fn truncate_path(path: &str) -> String {
     }
 }
 
+/// Where the working directory is relative to the project root
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectPath {
+    /// Inside the project: the name of its root directory and the path
+    /// below it, empty at the root itself
+    Inside { project: String, relative: String },
+    /// Somewhere the project doesn't contain, e.g. after a `cd /tmp`
+    Outside,
+}
+
+/// Locates `current_dir` in the project at `project_dir`, comparing whole
+/// components so `/src/api-v2` isn't inside `/src/api`. `None` without a
+/// project or when the project is the filesystem root.
+pub fn project_path(current_dir: &str, project_dir: &str) -> Option<ProjectPath> {
+    if project_dir.is_empty() {
+        return None;
+    }
+    let project_dir = Path::new(project_dir);
+    let project = project_dir.file_name()?.to_string_lossy().into_owned();
+
+    match Path::new(current_dir).strip_prefix(project_dir) {
+        Ok(relative) => Some(ProjectPath::Inside {
+            project,
+            relative: relative.to_string_lossy().into_owned(),
+        }),
+        Err(_) => Some(ProjectPath::Outside),
+    }
+}
+
+/// Shorter forms of a formatted directory, for narrow terminals: every
+/// component but the last cut to its first character (`~/s/g/api`, keeping
+/// the dot of hidden ones), then the last component alone
+pub fn compact_forms(directory: &str) -> [String; 2] {
+    let mut components: Vec<&str> = directory.split('/').collect();
+    let last = components.pop().unwrap_or_default();
+
+    let mut abbreviated: Vec<String> = components
+        .iter()
+        .map(|component| {
+            let keep = if component.starts_with('.') { 2 } else { 1 };
+            component.chars().take(keep).collect()
+        })
+        .collect();
+    abbreviated.push(last.to_string());
+
+    let last = if last.is_empty() { directory } else { last };
+    [abbreviated.join("/"), last.to_string()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -80,6 +129,36 @@ mod tests {
         assert_eq!(format_directory(path, Some(home)), "~/very/long/path/to/some/deeply/nested/directory");
     }
 
+    #[test]
+    fn test_compact_forms() {
+        assert_eq!(compact_forms("~/src/grabby/services/api"), ["~/s/g/s/api", "api"]);
+        assert_eq!(compact_forms("~/.config/claude-statusline"), ["~/.c/claude-statusline", "claude-statusline"]);
+        assert_eq!(compact_forms("/usr/local/bin"), ["/u/l/bin", "bin"]);
+        assert_eq!(compact_forms("~"), ["~", "~"]);
+        assert_eq!(compact_forms("/"), ["/", "/"]);
+    }
+
+    #[test]
+    fn test_project_path() {
+        let inside = |project: &str, relative: &str| {
+            Some(ProjectPath::Inside {
+                project: project.to_string(),
+                relative: relative.to_string(),
+            })
+        };
+        let project = "/Users/gak/src/monorepo";
+        assert_eq!(
+            project_path("/Users/gak/src/monorepo/services/billing/api", project),
+            inside("monorepo", "services/billing/api")
+        );
+        assert_eq!(project_path("/Users/gak/src/monorepo", project), inside("monorepo", ""));
+        assert_eq!(project_path("/Users/gak/src/monorepo/", "/Users/gak/src/monorepo/"), inside("monorepo", ""));
+        assert_eq!(project_path("/Users/gak/src/monorepo-v2", project), Some(ProjectPath::Outside));
+        assert_eq!(project_path("/tmp", project), Some(ProjectPath::Outside));
+        assert_eq!(project_path("/tmp", ""), None);
+        assert_eq!(project_path("/tmp", "/"), None);
+    }
+
     #[test]
     fn test_edge_case_empty_path() {
         assert_eq!(format_directory("", None), "");
@@ -0,0 +1,307 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+use crate::deadline::{output_until, Cancel};
+use crate::vcs::{VcsProvider, VcsStatus};
+
+#[derive(Debug, Clone)]
+pub struct GitInfo {
+    pub branch: Option<String>,
+    pub commit: Option<String>,
+    pub has_staged: bool,
+    pub has_unstaged: bool,
+    pub has_untracked: bool,
+    pub has_conflict: bool,
+    pub operation: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+impl GitInfo {
+    pub fn empty() -> Self {
+        Self {
+            branch: None,
+            commit: None,
+            has_staged: false,
+            has_unstaged: false,
+            has_untracked: false,
+            has_conflict: false,
+            operation: None,
+            ahead: 0,
+            behind: 0,
+        }
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.has_staged || self.has_unstaged || self.has_untracked
+    }
+
+    pub fn into_vcs_status(self) -> Option<VcsStatus> {
+        let commit = self.commit?;
+        Some(VcsStatus {
+            refs: self.branch.into_iter().collect(),
+            operation: self.operation,
+            conflict: self.has_conflict,
+            dirty: self.has_unstaged,
+            staged: self.has_staged,
+            untracked: self.has_untracked,
+            ahead: self.ahead,
+            behind: self.behind,
+            ..VcsStatus::new("git", &commit)
+        })
+    }
+
+    pub fn format(&self) -> Option<String> {
+        self.clone().into_vcs_status().map(|status| status.format())
+    }
+}
+
+pub struct GitProvider;
+
+impl VcsProvider for GitProvider {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn detect(&self, path: &Path) -> Option<PathBuf> {
+        find_repo_root(path)
+    }
+
+    fn status(&self, path: &Path, deadline: Instant, cancel: &Cancel) -> Option<VcsStatus> {
+        get_git_status(path.to_str()?, deadline, cancel).into_vcs_status()
+    }
+}
+
+/// git status for `path`; a `git` still running at `deadline`, or when
+/// `cancel` is cancelled, is killed
+pub fn get_git_status(path: &str, deadline: Instant, cancel: &Cancel) -> GitInfo {
+    // Avoid spawning git at all outside of a repository
+    let git_dir = match find_git_dir(Path::new(path)) {
+        Some(dir) => dir,
+        None => return GitInfo::empty(),
+    };
+
+    // `--no-optional-locks` keeps us from racing the user's own git commands
+    // over `index.lock` when status refreshes the index stat cache
+    let status_output = output_until(
+        Command::new("git")
+            .args(["--no-optional-locks", "status", "--porcelain=v2", "--branch"])
+            .current_dir(path),
+        deadline,
+        cancel,
+    );
+
+    let mut info = match status_output {
+        Ok(output) if output.status.success() => {
+            parse_porcelain_v2(&String::from_utf8_lossy(&output.stdout))
+        }
+        _ => return GitInfo::empty(),
+    };
+
+    info.operation = detect_operation(&git_dir);
+
+    info
+}
+
+fn find_repo_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Finds the git directory for `path`
+fn find_git_dir(path: &Path) -> Option<PathBuf> {
+    git_dir(&find_repo_root(path)?)
+}
+
+/// The git directory of the repository at `root`: `.git` itself, or where
+/// the `gitdir:` file in its place points for worktrees and submodules
+pub fn git_dir(root: &Path) -> Option<PathBuf> {
+    let dot_git = root.join(".git");
+
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+
+    let contents = fs::read_to_string(&dot_git).ok()?;
+    let target = contents.trim().strip_prefix("gitdir:")?.trim();
+    Some(dot_git.parent()?.join(target))
+}
+
+/// Names the in-progress multi-step operation, if any, from the marker files
+/// git leaves in its directory
+fn detect_operation(git_dir: &Path) -> Option<String> {
+    let operation = if git_dir.join("rebase-merge").is_dir() || git_dir.join("rebase-apply").is_dir() {
+        "rebase"
+    } else if git_dir.join("MERGE_HEAD").exists() {
+        "merge"
+    } else if git_dir.join("CHERRY_PICK_HEAD").exists() {
+        "cherry-pick"
+    } else if git_dir.join("REVERT_HEAD").exists() {
+        "revert"
+    } else if git_dir.join("BISECT_LOG").exists() {
+        "bisect"
+    } else {
+        return None;
+    };
+
+    Some(operation.to_string())
+}
+
+fn parse_porcelain_v2(output: &str) -> GitInfo {
+    let mut info = GitInfo::empty();
+
+    for line in output.lines() {
+        if let Some(header) = line.strip_prefix("# ") {
+            let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+            match key {
+                "branch.oid" if value != "(initial)" => {
+                    info.commit = Some(value.chars().take(7).collect());
+                }
+                "branch.oid" => {
+                    // Unborn branch: nothing committed yet
+                    info.commit = Some("initial".to_string());
+                }
+                "branch.head" if value != "(detached)" => {
+                    info.branch = Some(value.to_string());
+                }
+                "branch.ab" => {
+                    for count in value.split_whitespace() {
+                        if let Some(ahead) = count.strip_prefix('+') {
+                            info.ahead = ahead.parse().unwrap_or(0);
+                        } else if let Some(behind) = count.strip_prefix('-') {
+                            info.behind = behind.parse().unwrap_or(0);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let mut fields = line.splitn(3, ' ');
+        match (fields.next(), fields.next()) {
+            (Some("1"), Some(xy)) | (Some("2"), Some(xy)) => {
+                let mut xy = xy.chars();
+                if xy.next().is_some_and(|x| x != '.') {
+                    info.has_staged = true;
+                }
+                if xy.next().is_some_and(|y| y != '.') {
+                    info.has_unstaged = true;
+                }
+            }
+            (Some("u"), _) => info.has_conflict = true,
+            (Some("?"), _) => info.has_untracked = true,
+            _ => {}
+        }
+    }
+
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn deadline() -> Instant {
+        Instant::now() + Duration::from_secs(30)
+    }
+
+    #[test]
+    fn test_empty_git_info_formats_to_none() {
+        let info = GitInfo::empty();
+        assert_eq!(info.format(), None);
+    }
+
+    #[test]
+    fn test_parse_clean_branch_with_upstream() {
+        let output = "\
+# branch.oid 1234567890abcdef1234567890abcdef12345678
+# branch.head main
+# branch.upstream origin/main
+# branch.ab +2 -1
+";
+        let info = parse_porcelain_v2(output);
+        assert_eq!(info.branch.as_deref(), Some("main"));
+        assert_eq!(info.commit.as_deref(), Some("1234567"));
+        assert_eq!(info.ahead, 2);
+        assert_eq!(info.behind, 1);
+        assert!(!info.has_changes());
+        assert_eq!(info.format(), Some("1234567 main ↑2↓1".to_string()));
+    }
+
+    #[test]
+    fn test_parse_detached_head() {
+        let output = "\
+# branch.oid 1234567890abcdef1234567890abcdef12345678
+# branch.head (detached)
+";
+        let info = parse_porcelain_v2(output);
+        assert_eq!(info.branch, None);
+        assert_eq!(info.format(), Some("1234567".to_string()));
+    }
+
+    #[test]
+    fn test_parse_working_tree_state() {
+        let output = "\
+# branch.oid 1234567890abcdef1234567890abcdef12345678
+# branch.head main
+1 M. N... 100644 100644 100644 aaaaaaa bbbbbbb staged.rs
+1 .M N... 100644 100644 100644 aaaaaaa bbbbbbb unstaged.rs
+u UU N... 100644 100644 100644 100644 aaaaaaa bbbbbbb ccccccc conflicted.rs
+? untracked.rs
+";
+        let info = parse_porcelain_v2(output);
+        assert!(info.has_staged);
+        assert!(info.has_unstaged);
+        assert!(info.has_untracked);
+        assert!(info.has_conflict);
+        assert_eq!(info.format(), Some("1234567 main conflict+*?".to_string()));
+    }
+
+    #[test]
+    fn test_operation_in_format() {
+        let info = GitInfo {
+            branch: Some("main".to_string()),
+            commit: Some("1234567".to_string()),
+            has_conflict: true,
+            operation: Some("rebase".to_string()),
+            ..GitInfo::empty()
+        };
+        assert_eq!(info.format(), Some("1234567 main rebase conflict".to_string()));
+    }
+
+    #[test]
+    fn test_non_git_directory_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let info = get_git_status(dir.path().to_str().unwrap(), deadline(), &Cancel::default());
+        assert!(info.commit.is_none());
+    }
+
+    #[test]
+    fn test_git_status_of_fresh_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let initialized = Command::new("git")
+            .args(["init", "-q", "-b", "main"])
+            .current_dir(dir.path())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+        if !initialized {
+            // git isn't installed here; the parser tests above still apply
+            return;
+        }
+
+        std::fs::write(dir.path().join("notes.txt"), "hello").unwrap();
+        std::fs::write(dir.path().join(".git/MERGE_HEAD"), "").unwrap();
+
+        let info = get_git_status(dir.path().to_str().unwrap(), deadline(), &Cancel::default());
+        assert_eq!(info.branch.as_deref(), Some("main"));
+        assert_eq!(info.commit.as_deref(), Some("initial"));
+        assert!(info.has_untracked);
+        assert_eq!(info.operation.as_deref(), Some("merge"));
+    }
+}
@@ -1,25 +1,41 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
 
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+/// The JSON Claude Code pipes to the statusline. Only the working directory
+/// and the model's display name are required; everything else defaults to
+/// empty so older hosts and hand-written input still render.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct ClaudeInput {
+    #[serde(default)]
     pub session_id: String,
+    #[serde(default)]
     pub transcript_path: String,
+    #[serde(default)]
     pub cwd: String,
     pub model: ModelInfo,
     pub workspace: WorkspaceInfo,
+    #[serde(default)]
     pub version: String,
+    #[serde(default)]
     pub output_style: OutputStyle,
+    /// Fields this version doesn't know about, kept for `{extra.*}`
+    /// template placeholders
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct ModelInfo {
+    #[serde(default)]
     pub id: String,
     pub display_name: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 pub struct WorkspaceInfo {
     pub current_dir: String,
+    #[serde(default)]
     pub project_dir: String,
 }
 
@@ -28,12 +44,41 @@ pub struct OutputStyle {
     pub name: String,
 }
 
+impl Default for OutputStyle {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+        }
+    }
+}
+
 impl ClaudeInput {
-    pub fn from_stdin() -> Result<Self, Box<dyn std::error::Error>> {
+    /// Reads the session Claude Code sends; `None` when stdin was empty
+    pub fn from_stdin() -> Result<Option<Self>, Box<dyn std::error::Error>> {
         let mut input = String::new();
         std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)?;
+        if input.trim().is_empty() {
+            return Ok(None);
+        }
         let parsed: ClaudeInput = serde_json::from_str(&input)?;
-        Ok(parsed)
+        Ok(Some(parsed))
+    }
+
+    /// Looks up an unknown field by dotted path, e.g. `cost.total_cost_usd`.
+    /// Only strings, numbers and booleans have a text form.
+    pub fn extra_value(&self, path: &str) -> Option<String> {
+        let mut keys = path.split('.');
+        let mut value = self.extra.get(keys.next()?)?;
+        for key in keys {
+            value = value.get(key)?;
+        }
+
+        match value {
+            Value::String(text) => Some(text.clone()),
+            Value::Number(number) => Some(number.to_string()),
+            Value::Bool(flag) => Some(flag.to_string()),
+            Value::Null | Value::Array(_) | Value::Object(_) => None,
+        }
     }
 }
 
@@ -58,6 +103,7 @@ mod tests {
             output_style: OutputStyle {
                 name: "default".to_string(),
             },
+            extra: BTreeMap::new(),
         }
     }
 
@@ -141,4 +187,48 @@ mod tests {
         assert_eq!(parsed.workspace.current_dir, "/Users/gak/src/grabby/subdir");
         assert_eq!(parsed.workspace.project_dir, "/Users/gak/src/grabby");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_minimal_input_uses_defaults() {
+        // The example from the README
+        let json = r#"{"workspace":{"current_dir":"/Users/gak/src/grabby"},"model":{"display_name":"Claude 3.5 Sonnet"},"output_style":{"name":"default"}}"#;
+
+        let parsed: ClaudeInput = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.workspace.current_dir, "/Users/gak/src/grabby");
+        assert_eq!(parsed.model.display_name, "Claude 3.5 Sonnet");
+        assert_eq!(parsed.session_id, "");
+        assert_eq!(parsed.transcript_path, "");
+        assert_eq!(parsed.model.id, "");
+
+        let parsed: ClaudeInput =
+            serde_json::from_str(r#"{"workspace":{"current_dir":"/"},"model":{"display_name":"M"}}"#).unwrap();
+        assert_eq!(parsed.output_style.name, "default");
+    }
+
+    #[test]
+    fn test_essential_fields_are_required() {
+        let error = serde_json::from_str::<ClaudeInput>(r#"{"model":{"display_name":"M"}}"#).unwrap_err();
+        assert!(error.to_string().contains("missing field `workspace`"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_fields_are_kept() {
+        let json = r#"{
+            "workspace": {"current_dir": "/tmp"},
+            "model": {"display_name": "M"},
+            "exceeds_200k_tokens": false,
+            "cost": {"total_cost_usd": 0.42, "total_lines_added": 12},
+            "tags": ["a"]
+        }"#;
+
+        let parsed: ClaudeInput = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.extra_value("cost.total_cost_usd").as_deref(), Some("0.42"));
+        assert_eq!(parsed.extra_value("exceeds_200k_tokens").as_deref(), Some("false"));
+        assert_eq!(parsed.extra_value("cost"), None);
+        assert_eq!(parsed.extra_value("tags"), None);
+        assert_eq!(parsed.extra_value("cost.missing"), None);
+
+        let roundtrip: ClaudeInput = serde_json::from_str(&serde_json::to_string(&parsed).unwrap()).unwrap();
+        assert_eq!(roundtrip, parsed);
+    }
+}
@@ -1,5 +1,24 @@
+use jj_lib::config::{ConfigError, ConfigSource, StackedConfig};
+use jj_lib::fileset::{self, FilesetDiagnostics};
+use jj_lib::fsmonitor::FsmonitorSettings;
+use jj_lib::git_backend::GitBackend;
+use jj_lib::gitignore::{GitIgnoreError, GitIgnoreFile};
+use jj_lib::matchers::Matcher;
+use jj_lib::repo::{ReadonlyRepo, Repo, StoreFactories};
+use jj_lib::repo_path::RepoPathUiConverter;
+use jj_lib::settings::{ConfigResultExt, UserSettings};
+use jj_lib::working_copy::{SnapshotError, SnapshotOptions};
+use jj_lib::workspace::{default_working_copy_factories, DefaultWorkspaceLoaderFactory, WorkspaceLoaderFactory};
+use std::env;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
+use std::time::Instant;
 
+use crate::deadline::{output_until, Cancel};
+use crate::vcs::{VcsProvider, VcsStatus};
+
+#[derive(Debug, Clone)]
 pub struct JjInfo {
     pub change_id: Option<String>,
     pub bookmarks: Vec<String>,
@@ -18,9 +37,7 @@ impl JjInfo {
     }
 
     pub fn format(&self) -> Option<String> {
-        if self.change_id.is_none() {
-            return None;
-        }
+        self.change_id.as_ref()?;
 
         let mut parts = Vec::new();
         
@@ -44,30 +61,233 @@ impl JjInfo {
 
         Some(result)
     }
+
+    pub fn into_vcs_status(self) -> Option<VcsStatus> {
+        let change_id = self.change_id?;
+        Some(VcsStatus {
+            refs: self.bookmarks,
+            conflict: self.has_conflict,
+            dirty: self.has_changes,
+            ..VcsStatus::new("jj", &change_id)
+        })
+    }
 }
 
-pub fn get_jj_status(path: &str) -> JjInfo {
-    // For now, fall back to using jj commands until jj-lib API is clearer
-    // This maintains the same interface for future jj-lib integration
-    
-    // Check if this is a jj repository
-    let is_jj_repo = Command::new("jj")
-        .arg("root")
-        .current_dir(path)
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false);
-    
-    if !is_jj_repo {
-        return JjInfo::empty();
+pub struct JjProvider;
+
+impl VcsProvider for JjProvider {
+    fn name(&self) -> &'static str {
+        "jj"
     }
-    
+
+    fn detect(&self, path: &Path) -> Option<PathBuf> {
+        find_workspace_root(path)
+    }
+
+    fn status(&self, path: &Path, deadline: Instant, cancel: &Cancel) -> Option<VcsStatus> {
+        get_jj_status(path.to_str()?, deadline, cancel).into_vcs_status()
+    }
+}
+
+/// jj status for `path`. `jj` processes still running at `deadline`, or
+/// when `cancel` is cancelled, are killed. In-process reads can't be
+/// interrupted, so one that runs out of time stops before its snapshot
+/// instead; a snapshot already under way runs to the end.
+pub fn get_jj_status(path: &str, deadline: Instant, cancel: &Cancel) -> JjInfo {
+    // Only look for a workspace by walking up to the nearest `.jj` directory,
+    // so non-jj directories never pay for a process spawn
+    let workspace_root = match find_workspace_root(Path::new(path)) {
+        Some(root) => root,
+        None => return JjInfo::empty(),
+    };
+
+    // Prefer reading the repo in-process; the jj CLI is only a fallback for
+    // repos the library can't load (newer formats, custom backends, config
+    // it can't parse). The in-process read follows the same user and repo
+    // config as `jj`, but not jj-cli's `JJ_*` overrides or `--config` flags.
+    match get_jj_status_native(&workspace_root, deadline, cancel, &|name| env::var(name).ok()) {
+        Ok(info) => info,
+        Err(_) if out_of_time(deadline, cancel) => JjInfo::empty(),
+        Err(_) => get_jj_status_cli(path, deadline, cancel),
+    }
+}
+
+fn out_of_time(deadline: Instant, cancel: &Cancel) -> bool {
+    cancel.is_cancelled() || Instant::now() >= deadline
+}
+
+fn find_workspace_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| dir.join(".jj").is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// Reads the workspace with jj-lib, looking up environment variables through
+/// `var` to find the user's jj config
+fn get_jj_status_native(
+    workspace_root: &Path,
+    deadline: Instant,
+    cancel: &Cancel,
+    var: &dyn Fn(&str) -> Option<String>,
+) -> Result<JjInfo, Box<dyn std::error::Error>> {
+    let loader = DefaultWorkspaceLoaderFactory.create(workspace_root)?;
+    let settings = load_settings(loader.repo_path(), var)?;
+    let workspace = loader.load(&settings, &StoreFactories::default(), &default_working_copy_factories())?;
+    let repo = workspace.repo_loader().load_at_head(&settings)?;
+
+    let wc_commit_id = repo
+        .view()
+        .get_wc_commit_id(workspace.workspace_id())
+        .ok_or("workspace has no working-copy commit")?;
+    let wc_commit = repo.store().get_commit(wc_commit_id)?;
+
+    // Same as the CLI's `change_id.short()`: the first 12 reverse-hex digits
+    let change_id: String = wc_commit.change_id().reverse_hex().chars().take(12).collect();
+
+    let bookmarks = repo
+        .view()
+        .local_bookmarks_for_commit(wc_commit_id)
+        .map(|(name, _)| name.to_string())
+        .collect();
+
+    let has_conflict = wc_commit.has_conflict()?;
+
+    // The snapshot is the slow part, and can't be stopped once started;
+    // don't start one nobody waits for
+    if out_of_time(deadline, cancel) {
+        return Err("out of time".into());
+    }
+
+    // Snapshot the working copy so edits made since the last jj command are
+    // seen, like `jj diff` would. The lock is dropped without `finish()`, so
+    // nothing is recorded in the operation log.
+    let auto_track = auto_track_matcher(&settings, workspace.workspace_root())?;
+    let mut locked_wc = workspace.working_copy().start_mutation()?;
+    let options = SnapshotOptions {
+        base_ignores: base_ignores(&repo)?,
+        fsmonitor_settings: FsmonitorSettings::None,
+        progress: None,
+        start_tracking_matcher: auto_track.as_ref(),
+        max_new_file_size: settings.max_new_file_size()?,
+        conflict_marker_style: settings.conflict_marker_style()?,
+    };
+    let has_changes = match locked_wc.snapshot(&options) {
+        Ok(snapshot_tree_id) => snapshot_tree_id != wc_commit.parent_tree(repo.as_ref())?.id(),
+        // jj refuses to snapshot it, but a new file is still a change
+        Err(SnapshotError::NewFileTooLarge { .. }) => true,
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(JjInfo {
+        change_id: Some(change_id),
+        bookmarks,
+        has_conflict,
+        has_changes,
+    })
+}
+
+/// jj's settings for the repo at `repo_path`: the user's config, then the
+/// repo's own `config.toml` over it, loaded the way jj-cli loads them.
+/// jj-cli's built-in defaults aren't among them; `UserSettings` and
+/// [`auto_track_matcher`] fall back to the same values.
+fn load_settings(repo_path: &Path, var: &dyn Fn(&str) -> Option<String>) -> Result<UserSettings, ConfigError> {
+    let mut config = StackedConfig::empty();
+    if let Some(path) = user_config_path(var) {
+        if path.is_dir() {
+            config.load_dir(ConfigSource::User, path)?;
+        } else {
+            // A missing file is no config, as with jj
+            config.load_file(ConfigSource::User, path)?;
+        }
+    }
+    config.load_file(ConfigSource::Repo, repo_path.join("config.toml"))?;
+    Ok(UserSettings::from_config(config))
+}
+
+/// Where jj-cli looks for the user's config: `$JJ_CONFIG`, else
+/// `jj/config.toml` in the platform's config directory or `~/.jjconfig.toml`,
+/// whichever exists
+fn user_config_path(var: &dyn Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    let non_empty = |name| var(name).filter(|value| !value.is_empty());
+    if let Some(path) = non_empty("JJ_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    let home = non_empty("HOME").map(PathBuf::from);
+    let config_dir = if cfg!(target_os = "macos") {
+        home.as_ref().map(|home| home.join("Library/Application Support"))
+    } else {
+        non_empty("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home| home.join(".config")))
+    };
+
+    [config_dir.map(|dir| dir.join("jj/config.toml")), home.map(|home| home.join(".jjconfig.toml"))]
+        .into_iter()
+        .flatten()
+        .find(|path| path.exists())
+}
+
+/// New files jj starts tracking on its own, from `snapshot.auto-track`
+fn auto_track_matcher(settings: &UserSettings, workspace_root: &Path) -> Result<Box<dyn Matcher>, Box<dyn std::error::Error>> {
+    let pattern = settings
+        .get_string("snapshot.auto-track")
+        .optional()?
+        .unwrap_or_else(|| "all()".to_string());
+    let path_converter = RepoPathUiConverter::Fs {
+        cwd: workspace_root.to_path_buf(),
+        base: workspace_root.to_path_buf(),
+    };
+    let expression = fileset::parse(&mut FilesetDiagnostics::new(), &pattern, &path_converter)?;
+    Ok(expression.to_matcher())
+}
+
+/// The git excludes that `.gitignore` files in the working copy are chained
+/// onto, found the way jj-cli finds them: `core.excludesFile` (or
+/// `~/.config/git/ignore`) and, for git-backed repos, `info/exclude`
+fn base_ignores(repo: &ReadonlyRepo) -> Result<Arc<GitIgnoreFile>, GitIgnoreError> {
+    let mut ignores = GitIgnoreFile::empty();
+    match repo.store().backend_impl().downcast_ref::<GitBackend>() {
+        Some(git_backend) => {
+            if let Some(path) = excludes_file(&git_backend.git_repo().config_snapshot()) {
+                ignores = ignores.chain_with_file("", path)?;
+            }
+            ignores = ignores.chain_with_file("", git_backend.git_repo_path().join("info").join("exclude"))?;
+        }
+        None => {
+            if let Some(path) = gix::config::File::from_globals().ok().and_then(|config| excludes_file(&config)) {
+                ignores = ignores.chain_with_file("", path)?;
+            }
+        }
+    }
+    Ok(ignores)
+}
+
+fn excludes_file(config: &gix::config::File) -> Option<PathBuf> {
+    let Some(value) = config.string("core.excludesFile") else {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        return Some(config_home.join("git").join("ignore"));
+    };
+    let path = std::str::from_utf8(&value).ok()?;
+    match path.strip_prefix("~/") {
+        Some(relative) => Some(PathBuf::from(env::var_os("HOME")?).join(relative)),
+        None => Some(PathBuf::from(path)),
+    }
+}
+
+fn get_jj_status_cli(path: &str, deadline: Instant, cancel: &Cancel) -> JjInfo {
     // Get current revision info
-    let revision_output = Command::new("jj")
-        .args(&["log", "-r", "@", "--no-graph", "-T", 
-               "change_id.short() ++ \" \" ++ bookmarks.join(\" \") ++ if(conflict, \" conflict\", \"\")"])
-        .current_dir(path)
-        .output();
+    let revision_output = output_until(
+        Command::new("jj")
+            .args(["log", "-r", "@", "--no-graph", "-T",
+                   "change_id.short() ++ \" \" ++ bookmarks.join(\" \") ++ if(conflict, \" conflict\", \"\")"])
+            .current_dir(path),
+        deadline,
+        cancel,
+    );
         
     let revision_info = match revision_output {
         Ok(output) if output.status.success() => {
@@ -94,10 +314,7 @@ pub fn get_jj_status(path: &str) -> JjInfo {
         .collect();
     
     // Check for changes
-    let has_changes = Command::new("jj")
-        .args(&["diff", "--summary"])
-        .current_dir(path)
-        .output()
+    let has_changes = output_until(Command::new("jj").args(["diff", "--summary"]).current_dir(path), deadline, cancel)
         .map(|output| output.status.success() && !output.stdout.is_empty())
         .unwrap_or(false);
     
@@ -112,6 +329,18 @@ pub fn get_jj_status(path: &str) -> JjInfo {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::fake_env;
+    use jj_lib::workspace::Workspace;
+    use std::time::Duration;
+
+    fn deadline() -> Instant {
+        Instant::now() + Duration::from_secs(30)
+    }
+
+    /// The in-process status, away from the user's own jj config
+    fn native_status(workspace_root: &Path) -> Result<JjInfo, Box<dyn std::error::Error>> {
+        get_jj_status_native(workspace_root, deadline(), &Cancel::default(), &fake_env(&[]))
+    }
 
     #[test]
     fn test_empty_jj_info_formats_to_none() {
@@ -173,4 +402,98 @@ mod tests {
         };
         assert_eq!(info.format(), Some("abc123 main conflict*".to_string()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_non_jj_directory_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let info = get_jj_status(dir.path().to_str().unwrap(), deadline(), &Cancel::default());
+        assert!(info.change_id.is_none());
+    }
+
+    #[test]
+    fn test_native_status_of_fresh_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = UserSettings::from_config(StackedConfig::empty());
+        Workspace::init_internal_git(&settings, dir.path()).unwrap();
+
+        let info = native_status(dir.path()).unwrap();
+        assert_eq!(info.change_id.as_ref().map(String::len), Some(12));
+        assert!(info.bookmarks.is_empty());
+        assert!(!info.has_conflict);
+        assert!(!info.has_changes);
+
+        // New files are picked up by the snapshot, and from subdirectories too
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "fn main() {}").unwrap();
+        let info = get_jj_status(dir.path().join("src").to_str().unwrap(), deadline(), &Cancel::default());
+        assert!(info.has_changes);
+    }
+
+    #[test]
+    fn test_snapshot_honors_git_excludes_and_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = UserSettings::from_config(StackedConfig::empty());
+        let (_, repo) = Workspace::init_internal_git(&settings, dir.path()).unwrap();
+        let git_dir = repo.store().backend_impl().downcast_ref::<GitBackend>().unwrap().git_repo_path();
+        std::fs::create_dir_all(git_dir.join("info")).unwrap();
+        std::fs::write(git_dir.join("info/exclude"), "*.log\n").unwrap();
+
+        std::fs::write(dir.path().join("build.log"), "ignored").unwrap();
+        assert!(!native_status(dir.path()).unwrap().has_changes);
+
+        // Too big to snapshot, but still a change
+        std::fs::write(dir.path().join("dump.bin"), vec![0; 1024 * 1024 + 1]).unwrap();
+        assert!(native_status(dir.path()).unwrap().has_changes);
+    }
+
+    #[test]
+    fn test_snapshot_follows_repo_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = UserSettings::from_config(StackedConfig::empty());
+        let (workspace, _) = Workspace::init_internal_git(&settings, dir.path()).unwrap();
+        std::fs::write(workspace.repo_path().join("config.toml"), "snapshot.auto-track = 'glob:*.rs'\n").unwrap();
+
+        std::fs::write(dir.path().join("notes.txt"), "not tracked").unwrap();
+        assert!(!native_status(dir.path()).unwrap().has_changes);
+        std::fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        assert!(native_status(dir.path()).unwrap().has_changes);
+    }
+
+    #[test]
+    fn test_snapshot_follows_user_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = UserSettings::from_config(StackedConfig::empty());
+        Workspace::init_internal_git(&settings, dir.path()).unwrap();
+        let config = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(config.path(), "snapshot.auto-track = 'none()'\n").unwrap();
+        let env = fake_env(&[("JJ_CONFIG", config.path().to_str().unwrap())]);
+
+        std::fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        let info = get_jj_status_native(dir.path(), deadline(), &Cancel::default(), &env).unwrap();
+        assert!(!info.has_changes);
+    }
+
+    #[test]
+    fn test_user_config_path() {
+        assert_eq!(user_config_path(&fake_env(&[("JJ_CONFIG", "/etc/jj.toml")])), Some(PathBuf::from("/etc/jj.toml")));
+        // Neither exists
+        assert_eq!(user_config_path(&fake_env(&[("HOME", "/nonexistent")])), None);
+        assert_eq!(user_config_path(&fake_env(&[])), None);
+    }
+
+    #[test]
+    fn test_no_snapshot_once_out_of_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = UserSettings::from_config(StackedConfig::empty());
+        Workspace::init_internal_git(&settings, dir.path()).unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let passed = Instant::now();
+        assert!(get_jj_status_native(dir.path(), passed, &Cancel::default(), &fake_env(&[])).is_err());
+        let cancel = Cancel::default();
+        cancel.cancel();
+        assert!(get_jj_status_native(dir.path(), deadline(), &cancel, &fake_env(&[])).is_err());
+        // And no jj is run in its place
+        assert_eq!(get_jj_status(dir.path().to_str().unwrap(), passed, &cancel).change_id, None);
+    }
+}
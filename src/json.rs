@@ -0,0 +1,229 @@
+use serde::Serialize;
+use std::path::Path;
+
+use crate::output::StatusLine;
+use crate::vcs::{VcsStatus, PENDING};
+
+/// Version of the JSON schema. Fields may be added within a version;
+/// renaming or removing one, or changing its meaning, bumps it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The collected status for other tools (tmux, shell prompts, editor
+/// plugins), as written by `--output json`. Fields that weren't collected
+/// are `null`.
+#[derive(Debug, Serialize)]
+struct Status<'a> {
+    version: u32,
+    directory: Directory<'a>,
+    vcs: Option<Vcs<'a>>,
+    model: Model<'a>,
+    output_style: Option<&'a str>,
+    context: Option<Context>,
+    tokens: Option<Tokens>,
+    /// Estimated session spend in USD
+    cost: Option<f64>,
+    /// Why the status is degraded, e.g. `statusline: bad input`
+    warning: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Directory<'a> {
+    /// As Claude Code reported it
+    path: &'a str,
+    /// Home abbreviated and truncated, as the text line shows it
+    formatted: &'a str,
+    /// Root of the project Claude Code was started in
+    project_dir: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Vcs<'a> {
+    backend: &'a str,
+    /// `null` when the status didn't arrive in time and nothing is known
+    revision: Option<&'a str>,
+    refs: &'a [String],
+    operation: Option<&'a str>,
+    conflict: bool,
+    dirty: bool,
+    staged: bool,
+    untracked: bool,
+    ahead: usize,
+    behind: usize,
+    /// Root of the repository, when known
+    root: Option<&'a Path>,
+    /// The last known status, shown because collecting ran out of time
+    stale: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Model<'a> {
+    id: Option<&'a str>,
+    name: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Context {
+    used: u64,
+    window: u64,
+    percent: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Tokens {
+    input: u64,
+    output: u64,
+    cache_read: u64,
+    cache_write: u64,
+}
+
+impl<'a> From<&'a VcsStatus> for Vcs<'a> {
+    fn from(vcs: &'a VcsStatus) -> Self {
+        Self {
+            backend: &vcs.backend,
+            revision: Some(vcs.revision.as_str()).filter(|&revision| revision != PENDING),
+            refs: &vcs.refs,
+            operation: vcs.operation.as_deref(),
+            conflict: vcs.conflict,
+            dirty: vcs.dirty,
+            staged: vcs.staged,
+            untracked: vcs.untracked,
+            ahead: vcs.ahead,
+            behind: vcs.behind,
+            root: vcs.root.as_deref(),
+            stale: vcs.stale,
+        }
+    }
+}
+
+/// `status_line` as one line of JSON
+pub fn to_json(status_line: &StatusLine) -> String {
+    let status = Status {
+        version: SCHEMA_VERSION,
+        directory: Directory {
+            path: &status_line.current_dir,
+            formatted: &status_line.directory,
+            project_dir: non_empty(&status_line.project_dir),
+        },
+        vcs: status_line.vcs.as_ref().map(Vcs::from),
+        model: Model {
+            id: status_line.input.as_ref().and_then(|input| non_empty(&input.model.id)),
+            name: non_empty(&status_line.model_name),
+        },
+        output_style: status_line
+            .output_style
+            .as_deref()
+            .filter(|&style| style != "default" && style != "null"),
+        context: status_line.context.map(|context| Context {
+            used: context.used,
+            window: context.window,
+            percent: context.percent(),
+        }),
+        tokens: status_line.tokens.as_ref().map(|tokens| Tokens {
+            input: tokens.input_tokens,
+            output: tokens.output_tokens,
+            cache_read: tokens.cache_read_input_tokens,
+            cache_write: tokens.cache_creation_input_tokens,
+        }),
+        cost: status_line.cost,
+        warning: status_line.warning.as_deref(),
+    };
+    // Only borrowed strings, numbers and bools: serializing can't fail
+    serde_json::to_string(&status).unwrap_or_default()
+}
+
+fn non_empty(text: &str) -> Option<&str> {
+    Some(text).filter(|text| !text.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::{ContextUsage, TokenUsage};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_full_status() {
+        let input = serde_json::from_value(json!({
+            "model": {"id": "claude-opus-4-1", "display_name": "Opus"},
+            "workspace": {"current_dir": "/home/gak/src/grabby"},
+        }))
+        .unwrap();
+        let status_line = StatusLine {
+            current_dir: "/home/gak/src/grabby".to_string(),
+            project_dir: "/home/gak/src/grabby".to_string(),
+            directory: "~/src/grabby".to_string(),
+            vcs: Some(VcsStatus {
+                refs: vec!["main".to_string()],
+                dirty: true,
+                ahead: 2,
+                ..VcsStatus::new("jj", "abc123").with_root(Path::new("/home/gak/src/grabby"))
+            }),
+            model_name: "Opus".to_string(),
+            output_style: Some("Learning".to_string()),
+            context: Some(ContextUsage { used: 124_000, window: 200_000 }),
+            tokens: Some(TokenUsage {
+                input_tokens: 1000,
+                output_tokens: 500,
+                cache_read_input_tokens: 20_000,
+                cache_creation_input_tokens: 3000,
+            }),
+            cost: Some(1.84),
+            input: Some(input),
+            warning: None,
+        };
+
+        let value: Value = serde_json::from_str(&to_json(&status_line)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "version": 1,
+                "directory": {
+                    "path": "/home/gak/src/grabby",
+                    "formatted": "~/src/grabby",
+                    "project_dir": "/home/gak/src/grabby",
+                },
+                "vcs": {
+                    "backend": "jj",
+                    "revision": "abc123",
+                    "refs": ["main"],
+                    "operation": null,
+                    "conflict": false,
+                    "dirty": true,
+                    "staged": false,
+                    "untracked": false,
+                    "ahead": 2,
+                    "behind": 0,
+                    "root": "/home/gak/src/grabby",
+                    "stale": false,
+                },
+                "model": {"id": "claude-opus-4-1", "name": "Opus"},
+                "output_style": "Learning",
+                "context": {"used": 124000, "window": 200000, "percent": 62},
+                "tokens": {"input": 1000, "output": 500, "cache_read": 20000, "cache_write": 3000},
+                "cost": 1.84,
+                "warning": null,
+            })
+        );
+    }
+
+    #[test]
+    fn test_degraded_status() {
+        let status_line = StatusLine {
+            current_dir: "/tmp".to_string(),
+            directory: "/tmp".to_string(),
+            vcs: Some(VcsStatus::pending("git")),
+            output_style: Some("default".to_string()),
+            warning: Some("statusline: bad input".to_string()),
+            ..Default::default()
+        };
+
+        let value: Value = serde_json::from_str(&to_json(&status_line)).unwrap();
+        assert_eq!(value["directory"]["project_dir"], Value::Null);
+        assert_eq!(value["vcs"]["revision"], Value::Null);
+        assert_eq!(value["vcs"]["stale"], true);
+        assert_eq!(value["model"], json!({"id": null, "name": null}));
+        assert_eq!(value["output_style"], Value::Null);
+        assert_eq!(value["tokens"], Value::Null);
+        assert_eq!(value["warning"], "statusline: bad input");
+    }
+}
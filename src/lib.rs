@@ -1,4 +1,26 @@
 pub mod input;
+pub mod cli;
+pub mod config;
+pub mod color;
+pub mod theme;
+pub mod cache;
+pub mod debug_log;
+pub mod deadline;
 pub mod directory;
 pub mod jj_status;
-pub mod output;
\ No newline at end of file
+pub mod git_status;
+pub mod vcs;
+pub mod vcs_cache;
+pub mod watch;
+#[cfg(unix)]
+pub mod daemon;
+pub mod transcript;
+pub mod pricing;
+pub mod template;
+pub mod width;
+pub mod dialect;
+pub mod output;
+pub mod json;
+pub mod collect;
+#[cfg(test)]
+mod test_util;
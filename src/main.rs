@@ -1,47 +1,127 @@
+#[cfg(unix)]
+use claude_statusline::daemon;
 use claude_statusline::{
+    cli::{Args, OutputMode},
+    color::ColorDepth,
+    config::Config,
     input::ClaudeInput,
-    directory::format_directory,
-    jj_status::get_jj_status,
-    output::StatusLine,
+    cache::default_cache_dir,
+    debug_log::{default_log_path, log_error},
+    dialect::Dialect,
+    collect::{collect, collect_without_input},
+    json::to_json,
+    output::Target,
+    theme::Theme,
+    width::columns,
 };
 use std::env;
+use std::error::Error;
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
 use std::process;
+use std::time::Instant;
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
-        process::exit(1);
+    // Never exit with an error: Claude Code shows nothing at all then.
+    // Whatever fails, print the best line we can and log the details.
+    let started = Instant::now();
+    // Every problem is reported; the marker names them all
+    let mut problems = Vec::new();
+
+    let args = Args::parse(env::args().skip(1)).unwrap_or_else(|e| {
+        report("bad arguments", &e);
+        problems.push("bad arguments");
+        Args::default()
+    });
+
+    // The daemon is started by hand or a service manager, not by Claude
+    // Code, so it fails loudly
+    if args.daemon {
+        if let Err(e) = run_daemon() {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return;
     }
-}
 
-fn run() -> Result<(), Box<dyn std::error::Error>> {
-    // Parse JSON input from stdin
-    let input = ClaudeInput::from_stdin()?;
-    
-    // Format directory path
-    let home_dir = env::var("HOME").ok();
-    let directory = format_directory(&input.workspace.current_dir, home_dir.as_deref());
-    
-    // Get jj status
-    let jj_info = get_jj_status(&input.workspace.current_dir);
-    let jj_info_option = if jj_info.change_id.is_some() { Some(jj_info) } else { None };
-    
-    // Determine output style
-    let output_style = if input.output_style.name != "default" && input.output_style.name != "null" {
-        Some(input.output_style.name)
-    } else {
-        None
+    let config = Config::load(args.config.as_deref()).unwrap_or_else(|e| {
+        report("bad config", &e);
+        problems.push("bad config");
+        Config::default()
+    });
+
+    let theme = match args.theme.as_deref().or(config.theme.as_deref()) {
+        Some(name) => Theme::load(name, args.config.as_deref()).unwrap_or_else(|e| {
+            report("bad theme", &e);
+            problems.push("bad theme");
+            Theme::default()
+        }),
+        None => Theme::default(),
     };
-    
-    // Create and format status line
-    let status_line = StatusLine {
-        directory,
-        jj_info: jj_info_option,
-        model_name: input.model.display_name,
-        output_style,
+
+    // Prompts and tmux have no Claude Code session to send, and neither
+    // does whoever runs us by hand in a terminal
+    let deadline = started + config.timeout();
+    let cache_dir = default_cache_dir();
+    let reads_input = args.format.reads_input() && !args.no_input && !io::stdin().is_terminal();
+    let mut status_line = match reads_input.then(ClaudeInput::from_stdin) {
+        None => collect_without_input(&config, cache_dir.as_deref(), deadline),
+        Some(Ok(Some(input))) => collect(input, &config, cache_dir.as_deref(), deadline),
+        // Other tools asking for JSON may have nothing to send
+        Some(Ok(None)) if args.output == OutputMode::Json => collect_without_input(&config, cache_dir.as_deref(), deadline),
+        Some(Ok(None)) => {
+            report("bad input", &"nothing on stdin");
+            problems.push("bad input");
+            collect_without_input(&config, cache_dir.as_deref(), deadline)
+        }
+        Some(Err(e)) => {
+            report("bad input", &e);
+            problems.push("bad input");
+            collect_without_input(&config, cache_dir.as_deref(), deadline)
+        }
     };
-    
-    print!("{}", status_line.format());
-    
-    Ok(())
+    if !problems.is_empty() {
+        status_line.warning = Some(format!("statusline: {}", problems.join(", ")));
+    }
+
+    if args.output == OutputMode::Json {
+        print!("{}", to_json(&status_line));
+        return;
+    }
+
+    let target = Target {
+        width: args.width.or(config.width).or_else(columns),
+        color: args
+            .color
+            .or(config.color)
+            .unwrap_or_default()
+            .resolve(&|name| env::var(name).ok()),
+        // tmux maps colors onto each client terminal itself
+        depth: config.color_depth.unwrap_or_else(|| match args.format {
+            Dialect::Tmux => ColorDepth::TrueColor,
+            _ => ColorDepth::detect(&|name| env::var(name).ok()),
+        }),
+        theme,
+        dialect: args.format,
+    };
+    print!("{}", status_line.format_for(&config, target));
+}
+
+#[cfg(unix)]
+fn run_daemon() -> Result<(), Box<dyn Error>> {
+    let cache_dir = default_cache_dir().ok_or("no cache directory for the daemon socket")?;
+    daemon::run(&daemon::socket_path(&cache_dir))
+}
+
+#[cfg(not(unix))]
+fn run_daemon() -> Result<(), Box<dyn Error>> {
+    Err("the daemon needs Unix domain sockets".into())
+}
+
+/// Writes the full error to stderr and the debug log
+fn report(problem: &str, error: &dyn Display) {
+    eprintln!("Error: {}: {}", problem, error);
+    if let Some(path) = default_log_path() {
+        let _ = log_error(&path, problem, error);
+    }
 }
@@ -1,97 +1,680 @@
-use colored::*;
-use crate::jj_status::JjInfo;
+use std::collections::HashMap;
+use crate::color::{Color, ColorDepth};
+use crate::dialect::Dialect;
+use crate::config::{Align, Config, DirectoryAnchor, IconSet, SegmentConfig, SegmentKind, SeparatorStyle};
+use crate::directory::{self, ProjectPath};
+use crate::input::ClaudeInput;
+use crate::template::{self, Style, Template};
+use crate::theme::Theme;
+use crate::transcript::{ContextUsage, TokenUsage};
+use crate::vcs::{VcsStatus, PENDING};
+use crate::width::{display_width, truncate};
 
+const CONTEXT_WARN_PERCENT: u64 = 80; // Auto-compaction is getting close
+const COMPACT_REF_WIDTH: usize = 16; // Longest bookmark or branch name in a shortened VCS segment
+
+/// Icons and punctuation the line is drawn with
+struct Glyphs {
+    separator: &'static str,
+    ellipsis: &'static str,
+    ahead: &'static str,
+    behind: &'static str,
+    /// Between the project name and the path inside it
+    project: &'static str,
+    /// Before the directory when it's outside the project
+    outside: &'static str,
+    directory: &'static str,
+    vcs: &'static str,
+    vcs_dirty: &'static str,
+    model: &'static str,
+    output_style: &'static str,
+    context: &'static str,
+    cost: &'static str,
+    warning: &'static str,
+}
+
+const EMOJI: Glyphs = Glyphs {
+    separator: " ‧ ",
+    ellipsis: "…",
+    ahead: "↑",
+    behind: "↓",
+    project: " › ",
+    outside: "↗",
+    directory: "📂",
+    vcs: "🔀",       // Twisted arrows for clean state
+    vcs_dirty: "⚡", // Lightning for uncommitted changes
+    model: "🧠",
+    output_style: "🎭",
+    context: "📊",
+    cost: "💰",
+    warning: "⚠",
+};
+
+/// For terminals without emoji fonts: no icons, the text reads on its own
+const ASCII: Glyphs = Glyphs {
+    separator: " | ",
+    ellipsis: "...",
+    ahead: "^",
+    behind: "v",
+    project: " > ",
+    outside: "->",
+    directory: "",
+    vcs: "",
+    vcs_dirty: "",
+    model: "",
+    output_style: "",
+    context: "",
+    cost: "",
+    warning: "!",
+};
+
+/// Where the line is going: how many columns it may take, whether it may
+/// contain escape codes for colors and text attributes, how many colors
+/// the terminal shows, the theme it's drawn in, and the dialect the
+/// escape codes are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    pub width: Option<usize>,
+    pub color: bool,
+    pub depth: ColorDepth,
+    pub theme: Theme,
+    pub dialect: Dialect,
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Self {
+            width: None,
+            color: true,
+            depth: ColorDepth::TrueColor,
+            theme: Theme::default(),
+            dialect: Dialect::Ansi,
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct StatusLine {
+    /// The working directory as reported, before formatting
+    pub current_dir: String,
+    /// Root of the project Claude Code was started in, if reported
+    pub project_dir: String,
     pub directory: String,
-    pub jj_info: Option<JjInfo>,
+    pub vcs: Option<VcsStatus>,
     pub model_name: String,
     pub output_style: Option<String>,
+    /// How full the model's context window is
+    pub context: Option<ContextUsage>,
+    /// Session token totals from the transcript
+    pub tokens: Option<TokenUsage>,
+    /// Estimated session spend in USD
+    pub cost: Option<f64>,
+    /// Raw input from Claude Code, for template placeholders like `{version}`
+    pub input: Option<ClaudeInput>,
+    /// Shown first in red when the line is degraded, e.g. `statusline: bad input`
+    pub warning: Option<String>,
 }
 
 impl StatusLine {
     pub fn format(&self) -> String {
-        // Force colors to be enabled for statusline
-        colored::control::set_override(true);
-        
-        let mut parts = Vec::new();
+        self.format_with(&Config::default())
+    }
+
+    pub fn format_with(&self, config: &Config) -> String {
+        self.format_for(config, Target::default())
+    }
+
+    /// Formats the line for `target`, one line per row of segments. With a
+    /// width, each row is laid out on its own: right-aligned segments are
+    /// padded against the right edge, and a row whose groups don't fit side
+    /// by side becomes one left-aligned line, whose segments are shortened
+    /// first and dropped last, lowest priority first, until it fits. The
+    /// highest-priority segment of a row is always kept, so a very narrow
+    /// width can still overflow.
+    pub fn format_for(&self, config: &Config, target: Target) -> String {
+        // A template lays out its own line
+        let rows = match config.format {
+            Some(_) => vec![config.segments()],
+            None => config.rows(),
+        };
+        let lines: Vec<String> = rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                // The warning goes first on the first row
+                let warning = self.warning.as_deref().filter(|_| i == 0);
+                self.lay_out(config, target, row, warning)
+            })
+            .filter(|line| !line.is_empty())
+            .collect();
+        target.dialect.translate(&lines.join("\n"))
+    }
+
+    /// One row with its right-aligned segments against the right edge, when
+    /// the width is known and both groups fit at their full length
+    fn lay_out(&self, config: &Config, target: Target, row: &[SegmentKind], warning: Option<&str>) -> String {
+        let (right, left): (Vec<SegmentKind>, Vec<SegmentKind>) =
+            row.iter().partition(|&&kind| config.segment(kind).align == Some(Align::Right));
+
+        if let (Some(width), false, None) = (target.width, right.is_empty(), &config.format) {
+            let unlimited = Target { width: None, ..target };
+            let left = self.fit(config, unlimited, &left, warning);
+            let right = self.fit(config, unlimited, &right, None);
+            let used = display_width(&left) + display_width(&right);
+            // At least a space between the groups
+            let gap = if left.is_empty() { 0 } else { 1 };
+            if !right.is_empty() && used + gap <= width {
+                return format!("{}{}{}", left, " ".repeat(width - used), right);
+            }
+        }
+
+        // Too narrow (or no width to pad to): one left-aligned line
+        self.fit(config, target, row, warning)
+    }
+
+    /// One row in ANSI escape codes, fitted to the target's width
+    fn fit(&self, config: &Config, target: Target, row: &[SegmentKind], warning: Option<&str>) -> String {
+        let mut render = Render {
+            config,
+            target,
+            row,
+            warning,
+            // Links are escape codes too, so plain output has none
+            links: config.hyperlinks.unwrap_or(false) && target.color,
+            glyphs: match config.icons.unwrap_or_default() {
+                IconSet::Emoji => &EMOJI,
+                IconSet::Ascii => &ASCII,
+            },
+            fitting: Fitting::default(),
+        };
+        let mut line = self.render(&render);
+        let Some(width) = target.width else {
+            return line;
+        };
+
+        // Segments on the line, lowest priority first; on a tie the one
+        // further right goes first
+        let shown = match config.format {
+            Some(_) => SegmentKind::ALL.into_iter().filter(|&kind| config.segment(kind).enabled.unwrap_or(true)).collect(),
+            None => row.to_vec(),
+        };
+        let mut kinds: Vec<SegmentKind> = shown
+            .into_iter()
+            .rev()
+            .filter(|&kind| self.segment(&render, kind, &Style::default(), 0).is_some())
+            .collect();
+        kinds.sort_by_key(|&kind| config.priority(kind));
+
+        for &kind in &kinds {
+            for level in 1..=compact_levels(kind) {
+                if display_width(&line) <= width {
+                    return line;
+                }
+                render.fitting.compact.insert(kind, level);
+                line = self.render(&render);
+            }
+        }
+        for &kind in kinds.iter().take(kinds.len().saturating_sub(1)) {
+            if display_width(&line) <= width {
+                return line;
+            }
+            render.fitting.dropped.push(kind);
+            line = self.render(&render);
+        }
+        line
+    }
+
+    fn render(&self, render: &Render) -> String {
+        let config = render.config;
+        if let Some(caps) = block_caps(config, &render.target) {
+            return self.render_blocks(render, caps);
+        }
+
+        let separator = render.paint(
+            config.separator.as_deref().unwrap_or(render.glyphs.separator),
+            config.separator_color.unwrap_or(render.target.theme.separator),
+            &Style::default(),
+        );
+
+        // A template replaces the fixed order; it was validated when the
+        // config was loaded
+        let line = match config.format.as_deref().and_then(|f| Template::parse(f).ok()) {
+            Some(template) => template.render(&|name, style| self.variable(render, name, style)),
+            None => render
+                .row
+                .iter()
+                .copied()
+                .filter(|kind| !render.fitting.dropped.contains(kind))
+                .filter_map(|kind| self.segment(render, kind, &Style::default(), render.fitting.level(kind)))
+                .collect::<Vec<_>>()
+                .join(&separator),
+        };
+
+        match render.warning {
+            Some(warning) => {
+                let warning = with_icon(render.glyphs.warning, render.paint(warning, render.target.theme.warning, &Style::default()));
+                if line.is_empty() {
+                    warning
+                } else {
+                    format!("{}{}{}", warning, separator, line)
+                }
+            }
+            None => line,
+        }
+    }
+
+    /// The line as blocks of color, one per segment with the theme's text
+    /// color on top, joined by arrows or rounded caps. Each arrow takes the
+    /// color of the block before it over the background of the one after,
+    /// so neighbouring blocks blend; neighbours with the same color are split
+    /// by a thin arrow instead.
+    fn render_blocks(&self, render: &Render, caps: &BlockCaps) -> String {
+        let plain = Render {
+            target: Target { color: false, ..render.target },
+            fitting: render.fitting.clone(),
+            ..*render
+        };
+        let theme = &render.target.theme;
+
+        let mut blocks = Vec::new();
+        if let Some(warning) = render.warning {
+            blocks.push((with_icon(render.glyphs.warning, warning.to_string()), theme.warning));
+        }
+        for &kind in render.row {
+            if render.fitting.dropped.contains(&kind) {
+                continue;
+            }
+            if let Some(text) = self.segment(&plain, kind, &Style::default(), render.fitting.level(kind)) {
+                blocks.push((text, self.accent(render, kind, &Style::default())));
+            }
+        }
+
+        let depth = render.target.depth;
+        let text = theme.text.sgr(depth, false);
+        let mut line = String::new();
+        for (i, (content, color)) in blocks.iter().enumerate() {
+            let background = color.sgr(depth, true);
+            if i == 0 && !caps.start.is_empty() {
+                line.push_str(&format!("\x1b[{}m{}", color.sgr(depth, false), caps.start));
+            }
+            line.push_str(&format!("\x1b[{};{}m {} ", text, background, content));
+            match blocks.get(i + 1) {
+                Some((_, next)) if next.sgr(depth, true) == background => line.push_str(caps.thin),
+                Some((_, next)) => {
+                    line.push_str(&format!("\x1b[{};{}m{}", color.sgr(depth, false), next.sgr(depth, true), caps.end))
+                }
+                None => line.push_str(&format!("\x1b[0m\x1b[{}m{}\x1b[0m", color.sgr(depth, false), caps.end)),
+            }
+        }
+        line
+    }
+
+    /// Resolves a template placeholder
+    fn variable(&self, render: &Render, name: &str, style: &Style) -> Option<String> {
+        let kind = match name {
+            "directory" | "dir" => Some(SegmentKind::Directory),
+            "vcs" => Some(SegmentKind::Vcs),
+            "model" => Some(SegmentKind::Model),
+            "output_style" | "style" => Some(SegmentKind::OutputStyle),
+            "context" | "ctx" => Some(SegmentKind::Context),
+            "cost" => Some(SegmentKind::Cost),
+            _ => None,
+        };
+
+        if let Some(kind) = kind {
+            if !render.config.segment(kind).enabled.unwrap_or(true) || render.fitting.dropped.contains(&kind) {
+                return None;
+            }
+            return self.segment(render, kind, style, render.fitting.level(kind));
+        }
+
+        let value = match name {
+            "revision" => self.vcs.as_ref()?.revision.clone(),
+            "refs" => self.vcs.as_ref()?.refs.join(" "),
+            "backend" => self.vcs.as_ref()?.backend.clone(),
+            "model_id" => self.input.as_ref()?.model.id.clone(),
+            "session_id" => self.input.as_ref()?.session_id.clone(),
+            "version" => self.input.as_ref()?.version.clone(),
+            "cwd" => self.input.as_ref()?.cwd.clone(),
+            "project_dir" => self.input.as_ref()?.workspace.project_dir.clone(),
+            "context_percent" => self.context?.percent().to_string(),
+            "context_tokens" => self.context?.used.to_string(),
+            "input_tokens" => self.tokens?.input_tokens.to_string(),
+            "output_tokens" => self.tokens?.output_tokens.to_string(),
+            "cache_read_tokens" => self.tokens?.cache_read_input_tokens.to_string(),
+            "cache_creation_tokens" => self.tokens?.cache_creation_input_tokens.to_string(),
+            "cost_usd" => format!("{:.2}", self.cost?),
+            _ => self.input.as_ref()?.extra_value(name.strip_prefix(template::EXTRA_PREFIX)?)?,
+        };
 
-        // Dark grey separator dot - RGB(96, 96, 96)
-        let separator = " ‧ ".truecolor(96, 96, 96);
+        // Raw values are plain unless the placeholder asks for a style
+        Some(render.styled(&value, style.color.map(Color::from), style))
+    }
 
-        // Directory with folder emoji and space
-        // RGB(64, 224, 208) - Turquoise with punch
-        parts.push(format!("📂 {}", self.directory.truecolor(64, 224, 208)));
+    /// Renders one segment, shortened to its `compact` form (0 is the full
+    /// form, up to [`compact_levels`])
+    fn segment(&self, render: &Render, kind: SegmentKind, style: &Style, compact: usize) -> Option<String> {
+        let config: &SegmentConfig = render.config.segment(kind);
+        let glyphs = render.glyphs;
+        let icon = |default| config.icon.as_deref().unwrap_or(default);
+        let color = self.accent(render, kind, style);
 
-        // JJ info with dynamic emoji based on changes (no parentheses)
-        if let Some(jj_info) = &self.jj_info {
-            if jj_info.change_id.is_some() {
-                // Check if there are uncommitted changes
-                let emoji = if jj_info.has_changes {
-                    "⚡" // Lightning for uncommitted changes
+        match kind {
+            SegmentKind::Directory => {
+                let shorten = |path: &str| match compact {
+                    0 => path.to_string(),
+                    level => directory::compact_forms(path)[level.min(2) - 1].clone(),
+                };
+                let project = match config.anchor.unwrap_or_default() {
+                    DirectoryAnchor::Home => None,
+                    DirectoryAnchor::Project => directory::project_path(&self.current_dir, &self.project_dir),
+                };
+                let directory = match project {
+                    // The project name stays whole; only the path inside it
+                    // is shortened
+                    Some(ProjectPath::Inside { project, relative }) => {
+                        let project_style = Style { bold: true, ..style.clone() };
+                        let project_color = style.color.map(Color::from).or(config.project_color).unwrap_or(color);
+                        let mut directory = render.paint(&project, project_color, &project_style);
+                        if !relative.is_empty() {
+                            directory.push_str(&render.paint(glyphs.project, color, style));
+                            directory.push_str(&render.paint(&shorten(&relative), color, style));
+                        }
+                        directory
+                    }
+                    Some(ProjectPath::Outside) => {
+                        let marker = render.paint(glyphs.outside, render.target.theme.warning, style);
+                        format!("{} {}", marker, render.paint(&shorten(&self.directory), color, style))
+                    }
+                    None => render.paint(&shorten(&self.directory), color, style),
+                };
+                let url = Some(&self.current_dir).filter(|dir| !dir.is_empty()).map(|dir| file_url(dir));
+                Some(with_icon(icon(glyphs.directory), render.link(directory, url)))
+            }
+            SegmentKind::Vcs => {
+                let vcs = self.vcs.as_ref()?;
+
+                // Dynamic emoji based on changes (no parentheses)
+                let emoji = if vcs.has_changes() {
+                    config.dirty_icon.as_deref().unwrap_or(glyphs.vcs_dirty)
                 } else {
-                    "🔀" // Twisted arrows for clean state
+                    icon(glyphs.vcs)
                 };
-                
-                let mut jj_parts = Vec::new();
-                
-                // Add change ID in duller hot pink (greyer)
-                if let Some(change_id) = &jj_info.change_id {
-                    jj_parts.push(change_id.truecolor(200, 80, 140).to_string()); // Duller hot pink with more grey
+
+                let mut vcs_parts = Vec::new();
+
+                // Add revision (change ID / commit SHA) in duller hot pink (greyer)
+                // Revisions and refs link to the forge when configured
+                let repo = vcs.repo_name();
+                let forge = |id: &str| forge_url(config.link_url.as_deref()?, repo.as_deref(), id);
+
+                if vcs.revision == PENDING {
+                    vcs_parts.push(render.paint(glyphs.ellipsis, color, style));
+                } else {
+                    vcs_parts.push(render.link(render.paint(&vcs.revision, color, style), forge(&vcs.revision)));
+                }
+
+                // Add refs (bookmarks / branch) in full hot pink (brightest).
+                // Shortened to the first one, then left out, when narrow.
+                let ref_color = config.ref_color.unwrap_or(render.target.theme.vcs_ref);
+                match (compact, vcs.refs.as_slice()) {
+                    (_, []) => {}
+                    (0, refs) => {
+                        let bright_refs = refs
+                            .iter()
+                            .map(|name| render.link(render.paint(name, ref_color, style), forge(name)))
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        vcs_parts.push(bright_refs);
+                    }
+                    (1, [first, rest @ ..]) => {
+                        let shortened = truncate(first, COMPACT_REF_WIDTH, glyphs.ellipsis);
+                        let mut first = render.link(render.paint(&shortened, ref_color, style), forge(first));
+                        if !rest.is_empty() {
+                            first.push_str(&render.paint(&format!(" +{}", rest.len()), color, style));
+                        }
+                        vcs_parts.push(first);
+                    }
+                    _ => {}
+                }
+
+                // Add ahead/behind, operation and conflict indicators in duller hot pink
+                if let Some(divergence) = vcs.divergence().filter(|_| compact < 2) {
+                    let divergence = divergence.replace('↑', glyphs.ahead).replace('↓', glyphs.behind);
+                    vcs_parts.push(render.paint(&divergence, color, style));
+                }
+
+                if let Some(operation) = vcs.operation.as_ref().filter(|_| compact < 2) {
+                    vcs_parts.push(render.paint(operation, color, style));
+                }
+
+                if vcs.conflict {
+                    vcs_parts.push(render.paint("conflict", color, style)); // Same as change ID
                 }
-                
-                // Add bookmarks in full hot pink (brightest)
-                if !jj_info.bookmarks.is_empty() {
-                    let bright_bookmarks = jj_info.bookmarks
-                        .iter()
-                        .map(|bookmark| bookmark.truecolor(255, 20, 147).to_string()) // Full hot pink for branch names
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    jj_parts.push(bright_bookmarks);
+
+                let mut vcs_display = vcs_parts.join(" ");
+
+                // Add staged/dirty/untracked markers
+                vcs_display.push_str(&vcs.markers());
+
+                // Last known status while a refresh didn't finish in time
+                if vcs.stale && vcs.revision != PENDING {
+                    vcs_display.push(' ');
+                    vcs_display.push_str(&render.paint(glyphs.ellipsis, color, style));
                 }
-                
-                // Add conflict indicator in duller hot pink (greyer)
-                if jj_info.has_conflict {
-                    jj_parts.push("conflict".truecolor(200, 80, 140).to_string()); // Same as change ID
+
+                Some(with_icon(emoji, vcs_display))
+            }
+            SegmentKind::Model => {
+                if self.model_name.is_empty() {
+                    return None;
                 }
-                
-                let mut jj_display = jj_parts.join(" ");
-                
-                // Add asterisk for changes
-                if jj_info.has_changes {
-                    jj_display.push('*');
+                Some(with_icon(icon(glyphs.model), render.paint(&self.model_name, color, style)))
+            }
+            SegmentKind::OutputStyle => {
+                // Output style is only shown when not default (no brackets)
+                let output_style = self.output_style.as_ref()?;
+                if output_style == "default" || output_style == "null" {
+                    return None;
                 }
-                
-                parts.push(format!("{}{} {}", separator, emoji, jj_display));
+                Some(with_icon(icon(glyphs.output_style), render.paint(output_style, color, style)))
+            }
+            SegmentKind::Context => {
+                // Context window fill, switching to a warning color before
+                // auto-compaction kicks in
+                let percent = self.context?.percent();
+                let text = if compact == 0 { format!("{}% ctx", percent) } else { format!("{}%", percent) };
+                Some(with_icon(icon(glyphs.context), render.paint(&text, color, style)))
+            }
+            SegmentKind::Cost => {
+                let cost = self.cost?;
+                Some(with_icon(icon(glyphs.cost), render.paint(&format!("${:.2}", cost), color, style)))
             }
         }
+    }
 
-        // Model name with brain emoji and space
-        // RGB(255, 140, 0) - Vibrant orange
-        parts.push(format!("{}🧠 {}", separator, self.model_name.truecolor(255, 140, 0)));
+    /// The main color of a segment: the style's, the config's, or the
+    /// theme's. Context switches to a warning color before auto-compaction
+    /// kicks in.
+    fn accent(&self, render: &Render, kind: SegmentKind, style: &Style) -> Color {
+        let config = render.config.segment(kind);
+        let theme = &render.target.theme;
+        let (color, default) = match kind {
+            SegmentKind::Directory => (config.color, theme.directory),
+            SegmentKind::Vcs => (config.color, theme.vcs),
+            SegmentKind::Model => (config.color, theme.model),
+            SegmentKind::OutputStyle => (config.color, theme.output_style),
+            SegmentKind::Context => match self.context {
+                Some(context) if context.percent() >= CONTEXT_WARN_PERCENT => (config.warn_color, theme.context_warn),
+                _ => (config.color, theme.context),
+            },
+            SegmentKind::Cost => (config.color, theme.cost),
+        };
+        style.color.map(Color::from).or(color).unwrap_or(default)
+    }
+}
 
-        // Output style with theater masks emoji and space (no brackets)
-        // RGB(50, 205, 50) - Lime green
-        if let Some(style) = &self.output_style {
-            if style != "default" && style != "null" {
-                parts.push(format!("{}🎭 {}", separator, style.truecolor(50, 205, 50)));
-            }
+/// Glyphs around the blocks of the powerline and rounded styles
+struct BlockCaps {
+    /// Before the first block
+    start: &'static str,
+    /// After each block, in its color over the next one's
+    end: &'static str,
+    /// Between neighbouring blocks of the same color
+    thin: &'static str,
+}
+
+const POWERLINE: BlockCaps = BlockCaps {
+    start: "",
+    end: "\u{e0b0}",
+    thin: "\u{e0b1}",
+};
+
+const ROUNDED: BlockCaps = BlockCaps {
+    start: "\u{e0b6}",
+    end: "\u{e0b4}",
+    thin: "\u{e0b5}",
+};
+
+/// Caps for the separator style, or `None` for plain separators. Blocks
+/// need colors, and a template places its own separators.
+fn block_caps(config: &Config, target: &Target) -> Option<&'static BlockCaps> {
+    if !target.color || config.format.is_some() {
+        return None;
+    }
+    match config.separator_style.unwrap_or_default() {
+        SeparatorStyle::Plain => None,
+        SeparatorStyle::Powerline => Some(&POWERLINE),
+        SeparatorStyle::Rounded => Some(&ROUNDED),
+    }
+}
+
+/// Everything one rendering of the line depends on besides the status
+struct Render<'a> {
+    config: &'a Config,
+    target: Target,
+    /// Segments on the row being rendered, left to right
+    row: &'a [SegmentKind],
+    /// Shown first on the row
+    warning: Option<&'a str>,
+    links: bool,
+    glyphs: &'static Glyphs,
+    fitting: Fitting,
+}
+
+impl Render<'_> {
+    /// `text` as an OSC 8 hyperlink to `url`, when links are on
+    fn link(&self, text: String, url: Option<String>) -> String {
+        match url {
+            Some(url) if self.links => format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text),
+            _ => text,
+        }
+    }
+
+    fn paint(&self, text: &str, color: Color, style: &Style) -> String {
+        self.styled(text, Some(color), style)
+    }
+
+    /// `text` wrapped in SGR escape codes for `color` (at the target's
+    /// depth) and the style's attributes, or left plain when colors are off
+    fn styled(&self, text: &str, color: Option<Color>, style: &Style) -> String {
+        if !self.target.color {
+            return text.to_string();
+        }
+
+        let attributes = [(style.bold, "1"), (style.dimmed, "2"), (style.italic, "3"), (style.underline, "4")];
+        let mut codes: Vec<String> = attributes
+            .into_iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, code)| code.to_string())
+            .collect();
+        codes.extend(color.map(|color| color.sgr(self.target.depth, false)));
+
+        if codes.is_empty() {
+            text.to_string()
+        } else {
+            format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
         }
+    }
+}
+
+/// How far each segment was shortened to fit the width
+#[derive(Debug, Clone, Default)]
+struct Fitting {
+    compact: HashMap<SegmentKind, usize>,
+    dropped: Vec<SegmentKind>,
+}
+
+impl Fitting {
+    fn level(&self, kind: SegmentKind) -> usize {
+        self.compact.get(&kind).copied().unwrap_or(0)
+    }
+}
+
+/// Number of shorter forms a segment has: the directory abbreviated then
+/// reduced to its last component; VCS refs cut to the first one then left
+/// out along with ahead/behind and operations; context without "ctx"
+fn compact_levels(kind: SegmentKind) -> usize {
+    match kind {
+        SegmentKind::Directory | SegmentKind::Vcs => 2,
+        SegmentKind::Context => 1,
+        SegmentKind::Model | SegmentKind::OutputStyle | SegmentKind::Cost => 0,
+    }
+}
 
-        parts.join("")
+/// Icon followed by a space, or just the text when the icon is blank
+fn with_icon(icon: &str, text: String) -> String {
+    if icon.is_empty() {
+        text
+    } else {
+        format!("{} {}", icon, text)
     }
 }
 
+/// `file://` URL of a local path
+fn file_url(path: &str) -> String {
+    format!("file://{}", percent_encode(path))
+}
+
+/// Fills in a `link_url` template. `None` when it needs the repository
+/// name and that isn't known.
+fn forge_url(template: &str, repo: Option<&str>, id: &str) -> Option<String> {
+    let url = template.replace("{id}", &percent_encode(id));
+    if url.contains("{repo}") {
+        Some(url.replace("{repo}", &percent_encode(repo?)))
+    } else {
+        Some(url)
+    }
+}
+
+/// Escapes everything but unreserved characters and `/` (so paths and
+/// `feature/x` refs read naturally), which keeps control characters out
+/// of the escape sequence
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::git_status::GitInfo;
+    use crate::jj_status::JjInfo;
 
     #[test]
     fn test_format_minimal_statusline() {
         let status = StatusLine {
             directory: "~/src/grabby".to_string(),
-            jj_info: None,
+            vcs: None,
             model_name: "Claude 3.5 Sonnet".to_string(),
             output_style: Some("default".to_string()),
+            ..Default::default()
         };
         
         let formatted = status.format();
@@ -116,9 +699,10 @@ mod tests {
         
         let status = StatusLine {
             directory: "~/src/grabby".to_string(),
-            jj_info: Some(jj_info),
+            vcs: jj_info.into_vcs_status(),
             model_name: "Claude 3.5 Sonnet".to_string(),
             output_style: Some("default".to_string()),
+            ..Default::default()
         };
         
         let formatted = status.format();
@@ -140,9 +724,10 @@ mod tests {
     fn test_format_with_output_style() {
         let status = StatusLine {
             directory: "~/src/grabby".to_string(),
-            jj_info: None,
+            vcs: None,
             model_name: "Claude 3.5 Sonnet".to_string(),
             output_style: Some("Learning".to_string()),
+            ..Default::default()
         };
         
         let formatted = status.format();
@@ -166,9 +751,10 @@ mod tests {
         
         let status = StatusLine {
             directory: "~/src/grabby".to_string(),
-            jj_info: Some(jj_info),
+            vcs: jj_info.into_vcs_status(),
             model_name: "Claude 3.5 Sonnet".to_string(),
             output_style: Some("Explanatory".to_string()),
+            ..Default::default()
         };
         
         let formatted = status.format();
@@ -198,9 +784,10 @@ mod tests {
         
         let status = StatusLine {
             directory: "~/src/grabby".to_string(),
-            jj_info: Some(jj_info),
+            vcs: jj_info.into_vcs_status(),
             model_name: "Claude 3.5 Sonnet".to_string(),
             output_style: Some("default".to_string()),
+            ..Default::default()
         };
         
         let formatted = status.format();
@@ -220,9 +807,10 @@ mod tests {
     fn test_null_output_style_not_shown() {
         let status = StatusLine {
             directory: "~/src/grabby".to_string(),
-            jj_info: None,
+            vcs: None,
             model_name: "Claude 3.5 Sonnet".to_string(),
             output_style: Some("null".to_string()),
+            ..Default::default()
         };
         
         let formatted = status.format();
@@ -241,9 +829,10 @@ mod tests {
         
         let status_with_branch = StatusLine {
             directory: "~/src/grabby".to_string(),
-            jj_info: Some(jj_info_with_branch),
+            vcs: jj_info_with_branch.into_vcs_status(),
             model_name: "Claude 3.5 Sonnet".to_string(),
             output_style: None,
+            ..Default::default()
         };
         
         // Test without bookmarks (no branch) - only change ID in regular pink
@@ -256,9 +845,10 @@ mod tests {
         
         let status_no_branch = StatusLine {
             directory: "~/src/grabby".to_string(),
-            jj_info: Some(jj_info_no_branch),
+            vcs: jj_info_no_branch.into_vcs_status(),
             model_name: "Claude 3.5 Sonnet".to_string(),
             output_style: None,
+            ..Default::default()
         };
         
         let formatted_with_branch = status_with_branch.format();
@@ -279,4 +869,573 @@ mod tests {
         assert!(formatted_no_branch.contains("200;80;140"));     // Only duller hot pink for change ID
         assert!(!formatted_no_branch.contains("255;20;147"));    // No full hot pink without branches
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_format_with_git_info() {
+        let git_info = GitInfo {
+            branch: Some("main".to_string()),
+            commit: Some("1234567".to_string()),
+            has_unstaged: true,
+            ahead: 2,
+            ..GitInfo::empty()
+        };
+
+        let status = StatusLine {
+            directory: "~/src/grabby".to_string(),
+            vcs: git_info.into_vcs_status(),
+            model_name: "Claude 3.5 Sonnet".to_string(),
+            output_style: None,
+            ..Default::default()
+        };
+
+        let formatted = status.format();
+        assert!(formatted.contains("⚡ ")); // Dirty working tree
+        assert!(formatted.contains("main"));
+        assert!(formatted.contains("1234567"));
+        assert!(formatted.contains("↑2"));
+        assert!(formatted.contains("*"));
+        assert!(formatted.contains("Claude 3.5 Sonnet"));
+    }
+
+    #[test]
+    fn test_format_with_config_overrides() {
+        let config: Config = toml::from_str(
+            r##"
+            order = ["model", "directory"]
+            separator = " | "
+
+            [directory]
+            icon = ""
+            color = "#ffffff"
+            "##,
+        )
+        .unwrap();
+
+        let status = StatusLine {
+            directory: "~/src/grabby".to_string(),
+            vcs: None,
+            model_name: "Claude 3.5 Sonnet".to_string(),
+            output_style: Some("Learning".to_string()),
+            ..Default::default()
+        };
+
+        let formatted = status.format_with(&config);
+        assert!(formatted.find("Claude 3.5 Sonnet") < formatted.find("~/src/grabby"));
+        assert!(formatted.contains(" | "));
+        assert!(!formatted.contains("📂"));
+        assert!(!formatted.contains("‧"));
+        assert!(!formatted.contains("Learning")); // Not in the configured order
+    }
+
+    #[test]
+    fn test_format_with_template() {
+        let config = Config {
+            format: Some("{dir} {vcs?} {model:bold} ({style?}) v{version}".to_string()),
+            ..Config::default()
+        };
+
+        let input: ClaudeInput = serde_json::from_str(include_str!("../sample_input.json")).unwrap();
+        let status = StatusLine {
+            directory: "~/src/grabby".to_string(),
+            vcs: None,
+            model_name: "Claude 3.5 Sonnet".to_string(),
+            output_style: None,
+            input: Some(input),
+            ..Default::default()
+        };
+
+        let formatted = status.format_with(&config);
+        assert!(formatted.starts_with("📂 "));
+        assert!(formatted.contains("~/src/grabby"));
+        assert!(formatted.contains("\x1b[1;")); // Bold model name
+        assert!(formatted.contains("Claude 3.5 Sonnet"));
+        assert!(formatted.ends_with(" v1.0.71")); // Empty section and optional are gone
+        assert!(!formatted.contains("🔀"));
+        assert!(!formatted.contains("‧")); // Template replaces the separator
+        assert!(!formatted.contains("()"));
+    }
+
+    #[test]
+    fn test_template_reaches_extra_input_fields() {
+        let config = Config {
+            format: Some("{model} ({extra.cost.total_lines_added} lines) {extra.not_sent?}".to_string()),
+            ..Config::default()
+        };
+
+        let json = r#"{"workspace":{"current_dir":"/tmp"},"model":{"display_name":"Sonnet"},"cost":{"total_lines_added":156}}"#;
+        let status = StatusLine {
+            model_name: "Sonnet".to_string(),
+            input: Some(serde_json::from_str(json).unwrap()),
+            ..Default::default()
+        };
+
+        let formatted = status.format_with(&config);
+        assert!(formatted.ends_with("Sonnet\x1b[0m 156 lines"), "{:?}", formatted);
+    }
+
+    #[test]
+    fn test_format_with_context() {
+        let mut status = StatusLine {
+            directory: "~/src/grabby".to_string(),
+            model_name: "Claude 3.5 Sonnet".to_string(),
+            context: Some(ContextUsage { used: 124_000, window: 200_000 }),
+            ..Default::default()
+        };
+
+        let formatted = status.format();
+        assert!(formatted.contains("📊 "));
+        assert!(formatted.contains("62% ctx"));
+        assert!(formatted.find("Claude 3.5 Sonnet") < formatted.find("62% ctx"));
+        assert!(!formatted.contains("255;69;0"));
+
+        status.context = Some(ContextUsage { used: 170_000, window: 200_000 });
+        let formatted = status.format();
+        assert!(formatted.contains("85% ctx"));
+        assert!(formatted.contains("255;69;0")); // Warning color near auto-compaction
+    }
+
+    #[test]
+    fn test_format_with_cost() {
+        let status = StatusLine {
+            directory: "~/src/grabby".to_string(),
+            model_name: "Claude 3.5 Sonnet".to_string(),
+            cost: Some(1.8412),
+            ..Default::default()
+        };
+
+        let formatted = status.format();
+        assert!(formatted.contains("💰 "));
+        assert!(formatted.contains("$1.84"));
+        assert!(formatted.find("Claude 3.5 Sonnet") < formatted.find("$1.84"));
+    }
+
+    #[test]
+    fn test_format_degraded_with_warning() {
+        // Fallback after bad input: no model, just the directory
+        let status = StatusLine {
+            directory: "~/src/grabby".to_string(),
+            warning: Some("statusline: bad input".to_string()),
+            ..Default::default()
+        };
+
+        let formatted = status.format();
+        assert!(formatted.starts_with("⚠ \x1b[38;2;255;59;48mstatusline: bad input\x1b[0m"), "{:?}", formatted);
+        assert!(formatted.contains(" ‧ "));
+        assert!(formatted.contains("~/src/grabby"));
+        assert!(!formatted.contains("🧠"));
+    }
+
+    #[test]
+    fn test_format_with_stale_vcs_status() {
+        let stale = StatusLine {
+            directory: "~/src/grabby".to_string(),
+            vcs: Some(VcsStatus {
+                refs: vec!["main".to_string()],
+                stale: true,
+                ..VcsStatus::new("jj", "abc123")
+            }),
+            model_name: "Claude 3.5 Sonnet".to_string(),
+            ..Default::default()
+        };
+        assert!(stale.format().contains("\x1b[38;2;255;20;147mmain\x1b[0m \x1b[38;2;200;80;140m…\x1b[0m"));
+
+        let pending = StatusLine {
+            vcs: Some(VcsStatus::pending("jj")),
+            ..stale
+        };
+        assert!(pending.format().contains("🔀 \x1b[38;2;200;80;140m…\x1b[0m\x1b[38;2;96;96;96m ‧"));
+    }
+
+    fn wide_status_line() -> StatusLine {
+        StatusLine {
+            directory: "~/src/grabby/services/billing".to_string(),
+            vcs: Some(VcsStatus {
+                refs: vec!["feature/very-long-branch-name".to_string(), "main".to_string()],
+                ahead: 2,
+                ..VcsStatus::new("git", "1234567")
+            }),
+            model_name: "Claude 3.5 Sonnet".to_string(),
+            output_style: Some("Learning".to_string()),
+            context: Some(ContextUsage { used: 124_000, window: 200_000 }),
+            cost: Some(1.84),
+            ..Default::default()
+        }
+    }
+
+    fn width(columns: usize) -> Target {
+        Target {
+            width: Some(columns),
+            ..Target::default()
+        }
+    }
+
+    fn plain(line: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn test_format_to_width_fits_untouched() {
+        let status = wide_status_line();
+        assert_eq!(status.format_for(&Config::default(), Target { width: Some(500), ..Target::default() }), status.format());
+        assert_eq!(status.format_for(&Config::default(), Target::default()), status.format());
+    }
+
+    #[test]
+    fn test_format_to_width_shortens_before_dropping() {
+        let status = wide_status_line();
+        let config = Config::default();
+        let full = display_width(&status.format());
+
+        // Shortening the lowest-priority segment that can be shortened
+        // comes first: the context loses "ctx"
+        let line = status.format_for(&config, width(full - 1));
+        assert!(plain(&line).contains("📊 62% ‧"), "{}", plain(&line));
+        assert!(display_width(&line) < full);
+
+        // Then bookmarks are cut to the first one, then left out along with
+        // ahead/behind, before the directory is shortened
+        let line = plain(&status.format_for(&config, width(full - 15)));
+        assert!(line.contains("1234567 feature/very-lo… +1"), "{}", line);
+        assert!(line.contains("~/src/grabby/services/billing"));
+
+        let line = plain(&status.format_for(&config, width(95)));
+        assert!(line.contains("🔀 1234567 ‧"), "{}", line);
+        assert!(line.contains("~/s/g/s/billing"), "{}", line);
+        assert!(line.contains("🎭 Learning")); // Nothing dropped yet
+        assert!(display_width(&line) <= 95);
+    }
+
+    #[test]
+    fn test_format_to_width_drops_lowest_priority_last() {
+        let status = wide_status_line();
+        let config = Config::default();
+
+        let line = plain(&status.format_for(&config, width(60)));
+        assert!(!line.contains("Learning"), "{}", line); // Output style goes first
+        assert!(line.contains("billing"));
+        assert!(display_width(&line) <= 60);
+
+        // Only the most important segment is left, at its shortest
+        let line = plain(&status.format_for(&config, width(5)));
+        assert_eq!(line, "📂 billing");
+    }
+
+    #[test]
+    fn test_format_to_width_follows_configured_priorities() {
+        let status = wide_status_line();
+        let config: Config = toml::from_str("[output_style]\npriority = 99").unwrap();
+        let line = plain(&status.format_for(&config, width(5)));
+        assert_eq!(line, "🎭 Learning");
+    }
+
+    #[test]
+    fn test_format_to_width_with_template() {
+        let status = wide_status_line();
+        let config = Config {
+            format: Some("{dir} {vcs?} {style?}".to_string()),
+            ..Config::default()
+        };
+        let line = plain(&status.format_for(&config, width(30)));
+        assert_eq!(line, "📂 billing 🔀 1234567");
+    }
+
+    #[test]
+    fn test_format_without_color() {
+        let status = wide_status_line();
+        let target = Target {
+            color: false,
+            ..Target::default()
+        };
+        let line = status.format_for(&Config::default(), target);
+        assert!(!line.contains('\x1b'), "{:?}", line);
+        assert_eq!(line, plain(&status.format()));
+
+        // Template attributes are escape codes too
+        let config = Config {
+            format: Some("{model:bold} {revision:underline}".to_string()),
+            ..Config::default()
+        };
+        assert_eq!(status.format_for(&config, target), "🧠 Claude 3.5 Sonnet 1234567");
+    }
+
+    #[test]
+    fn test_format_with_ascii_icons() {
+        let mut status = wide_status_line();
+        status.vcs.as_mut().unwrap().stale = true;
+        status.vcs.as_mut().unwrap().dirty = true;
+        status.warning = Some("statusline: bad input".to_string());
+        let config: Config = toml::from_str("icons = \"ascii\"\n[cost]\nicon = \"$\"").unwrap();
+
+        let line = plain(&status.format_with(&config));
+        assert_eq!(
+            line,
+            "! statusline: bad input | ~/src/grabby/services/billing | 1234567 feature/very-long-branch-name main ^2* ... \
+             | Claude 3.5 Sonnet | Learning | 62% ctx | $ $1.84"
+        );
+        assert!(line.is_ascii());
+
+        let line = plain(&status.format_for(&config, width(display_width(&line) - 15)));
+        assert!(line.contains("1234567 feature/very-... +1"), "{}", line);
+        assert!(line.is_ascii());
+    }
+
+    #[test]
+    fn test_format_at_lower_color_depths() {
+        let status = StatusLine {
+            directory: "~/src/grabby".to_string(),
+            vcs: Some(VcsStatus {
+                refs: vec!["main".to_string()],
+                ..VcsStatus::new("jj", "abc123")
+            }),
+            ..Default::default()
+        };
+        let depth = |depth| Target {
+            depth,
+            ..Target::default()
+        };
+
+        let line = status.format_for(&Config::default(), depth(ColorDepth::Ansi256));
+        assert!(line.contains("\x1b[38;5;198mmain\x1b[0m"), "{:?}", line);
+        assert!(!line.contains("38;2;"));
+
+        let line = status.format_for(&Config::default(), depth(ColorDepth::Ansi16));
+        assert!(line.contains("\x1b[35mmain\x1b[0m"), "{:?}", line);
+
+        // Pinned colors win over the nearest one
+        let config: Config =
+            toml::from_str("[vcs]\nref_color = { rgb = \"#ff1493\", ansi16 = \"bright_magenta\" }").unwrap();
+        let line = status.format_for(&config, depth(ColorDepth::Ansi16));
+        assert!(line.contains("\x1b[95mmain\x1b[0m"), "{:?}", line);
+    }
+
+    #[test]
+    fn test_format_with_theme() {
+        let status = StatusLine {
+            directory: "~/src/grabby".to_string(),
+            model_name: "Opus".to_string(),
+            ..Default::default()
+        };
+        let target = Target {
+            theme: crate::theme::GRUVBOX,
+            ..Target::default()
+        };
+
+        let line = status.format_for(&Config::default(), target);
+        assert!(line.contains("\x1b[38;2;142;192;124m~/src/grabby\x1b[0m"), "{:?}", line);
+        assert!(line.contains("\x1b[38;2;254;128;25mOpus\x1b[0m"), "{:?}", line);
+
+        // Segment colors from the config still win
+        let config: Config = toml::from_str("[model]\ncolor = \"#112233\"").unwrap();
+        let line = status.format_for(&config, target);
+        assert!(line.contains("\x1b[38;2;17;34;51mOpus\x1b[0m"), "{:?}", line);
+    }
+
+    #[test]
+    fn test_powerline_blocks_blend() {
+        let status = StatusLine {
+            directory: "~/src".to_string(),
+            model_name: "Opus".to_string(),
+            ..Default::default()
+        };
+        let config: Config = toml::from_str("separator_style = \"powerline\"\n[directory]\nicon = \"\"\n[model]\nicon = \"\"").unwrap();
+        let target = Target {
+            depth: ColorDepth::Ansi16,
+            ..Target::default()
+        };
+
+        // Turquoise (cyan) then orange (yellow) at 16 colors, black text
+        assert_eq!(
+            status.format_for(&config, target),
+            "\x1b[30;46m ~/src \x1b[36;43m\u{e0b0}\x1b[30;43m Opus \x1b[0m\x1b[33m\u{e0b0}\x1b[0m"
+        );
+
+        // Same color on both sides: a thin arrow over the shared background
+        let config: Config = toml::from_str(
+            "separator_style = \"powerline\"\n[directory]\nicon = \"\"\n[model]\nicon = \"\"\ncolor = \"#40e0d0\"",
+        )
+        .unwrap();
+        assert_eq!(
+            status.format_for(&config, target),
+            "\x1b[30;46m ~/src \u{e0b1}\x1b[30;46m Opus \x1b[0m\x1b[36m\u{e0b0}\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_rounded_blocks() {
+        let status = StatusLine {
+            directory: "~/src".to_string(),
+            warning: Some("statusline: bad input".to_string()),
+            ..Default::default()
+        };
+        let config: Config = toml::from_str("separator_style = \"rounded\"\nicons = \"ascii\"").unwrap();
+        let target = Target {
+            depth: ColorDepth::Ansi16,
+            ..Target::default()
+        };
+
+        let line = status.format_for(&config, target);
+        assert!(line.starts_with("\x1b[91m\u{e0b6}\x1b[30;101m ! statusline: bad input \x1b[91;46m\u{e0b4}"), "{:?}", line);
+        assert!(line.ends_with("\x1b[30;46m ~/src \x1b[0m\x1b[36m\u{e0b4}\x1b[0m"), "{:?}", line);
+    }
+
+    #[test]
+    fn test_blocks_need_colors() {
+        let status = wide_status_line();
+        let config: Config = toml::from_str("separator_style = \"powerline\"").unwrap();
+        let plain_config: Config = toml::from_str("").unwrap();
+        let target = Target {
+            color: false,
+            ..Target::default()
+        };
+        assert_eq!(status.format_for(&config, target), status.format_for(&plain_config, target));
+    }
+
+    #[test]
+    fn test_hyperlinks() {
+        let status = StatusLine {
+            current_dir: "/home/gak/src/my repo".to_string(),
+            directory: "~/src/my repo".to_string(),
+            vcs: Some(VcsStatus {
+                refs: vec!["feature/x".to_string()],
+                ..VcsStatus::new("jj", "abc123").with_root(std::path::Path::new("/home/gak/src/my repo"))
+            }),
+            ..Default::default()
+        };
+
+        // Off by default
+        assert!(!status.format().contains("\x1b]8;"));
+
+        let config: Config = toml::from_str(
+            "hyperlinks = true\n[vcs]\nlink_url = \"https://forge/{repo}/commit/{id}\"",
+        )
+        .unwrap();
+        let line = status.format_with(&config);
+        assert!(
+            line.contains("\x1b]8;;file:///home/gak/src/my%20repo\x1b\\\x1b[38;2;64;224;208m~/src/my repo\x1b[0m\x1b]8;;\x1b\\"),
+            "{:?}",
+            line
+        );
+        assert!(line.contains("\x1b]8;;https://forge/my%20repo/commit/abc123\x1b\\"), "{:?}", line);
+        assert!(line.contains("\x1b]8;;https://forge/my%20repo/commit/feature/x\x1b\\"), "{:?}", line);
+        assert_eq!(display_width(&line), display_width(&status.format()));
+
+        // Without a link_url only the directory links
+        let config: Config = toml::from_str("hyperlinks = true").unwrap();
+        assert_eq!(status.format_with(&config).matches("\x1b]8;;\x1b\\").count(), 1);
+
+        // Plain output stays plain
+        let target = Target {
+            color: false,
+            ..Target::default()
+        };
+        assert!(!status.format_for(&config, target).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_rows_are_fitted_independently() {
+        let mut status = wide_status_line();
+        status.warning = Some("statusline: bad input".to_string());
+        let config: Config = toml::from_str(
+            "[model]\nrow = 2\n[output_style]\nrow = 2\n[context]\nrow = 2\n[cost]\nrow = 2",
+        )
+        .unwrap();
+
+        let line = status.format_with(&config);
+        let rows: Vec<String> = line.lines().map(plain).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].starts_with("⚠ statusline: bad input ‧ 📂 "), "{}", rows[0]);
+        assert_eq!(rows[1], "🧠 Claude 3.5 Sonnet ‧ 🎭 Learning ‧ 📊 62% ctx ‧ 💰 $1.84");
+
+        // The long first row is shortened without touching the second
+        status.warning = None;
+        let line = status.format_for(&config, width(58));
+        let rows: Vec<String> = line.lines().map(plain).collect();
+        assert_eq!(rows, ["📂 ~/src/grabby/services/billing ‧ 🔀 1234567", "🧠 Claude 3.5 Sonnet ‧ 🎭 Learning ‧ 📊 62% ctx ‧ 💰 $1.84"]);
+
+        // A row with nothing to show is left out
+        status.vcs = None;
+        status.directory = String::new();
+        let config: Config = toml::from_str("order = [\"vcs\", \"model\"]\n[model]\nrow = 2").unwrap();
+        assert_eq!(plain(&status.format_with(&config)), "🧠 Claude 3.5 Sonnet");
+    }
+
+    #[test]
+    fn test_right_aligned_group() {
+        let status = StatusLine {
+            directory: "~/src/grabby".to_string(),
+            model_name: "Opus".to_string(),
+            cost: Some(1.84),
+            ..Default::default()
+        };
+        let config: Config = toml::from_str(
+            "order = [\"directory\", \"model\", \"cost\"]\n[model]\nalign = \"right\"\n[cost]\nalign = \"right\"",
+        )
+        .unwrap();
+
+        let line = plain(&status.format_for(&config, width(40)));
+        assert_eq!(line, format!("📂 ~/src/grabby{}🧠 Opus ‧ 💰 $1.84", " ".repeat(7)));
+        assert_eq!(display_width(&line), 40);
+
+        // Too narrow for both groups: one line, fitted as usual
+        assert_eq!(plain(&status.format_for(&config, width(33))), "📂 grabby ‧ 🧠 Opus ‧ 💰 $1.84");
+        // Nothing to pad to
+        assert_eq!(plain(&status.format_with(&config)), "📂 ~/src/grabby ‧ 🧠 Opus ‧ 💰 $1.84");
+
+        // Only a right group
+        let config: Config = toml::from_str(
+            "order = [\"model\", \"cost\"]\n[model]\nalign = \"right\"\n[cost]\nalign = \"right\"",
+        )
+        .unwrap();
+        assert_eq!(plain(&status.format_for(&config, width(20))), "  🧠 Opus ‧ 💰 $1.84");
+    }
+
+    #[test]
+    fn test_project_relative_directory() {
+        let mut status = StatusLine {
+            current_dir: "/Users/gak/src/monorepo/services/billing/api".to_string(),
+            project_dir: "/Users/gak/src/monorepo".to_string(),
+            directory: "~/src/monorepo/services/billing/api".to_string(),
+            ..Default::default()
+        };
+        let config: Config = toml::from_str("[directory]\nanchor = \"project\"").unwrap();
+
+        // Home-relative unless asked
+        assert_eq!(plain(&status.format()), "📂 ~/src/monorepo/services/billing/api");
+
+        let line = status.format_with(&config);
+        assert_eq!(plain(&line), "📂 monorepo › services/billing/api");
+        assert!(line.contains("\x1b[1;38;2;64;224;208mmonorepo\x1b[0m"), "{:?}", line);
+
+        // Shortening keeps the project name whole
+        assert_eq!(plain(&status.format_for(&config, width(24))), "📂 monorepo › s/b/api");
+        assert_eq!(plain(&status.format_for(&config, width(18))), "📂 monorepo › api");
+
+        let config: Config =
+            toml::from_str("icons = \"ascii\"\n[directory]\nanchor = \"project\"\nproject_color = \"#ffffff\"").unwrap();
+        let line = status.format_with(&config);
+        assert_eq!(plain(&line), "monorepo > services/billing/api");
+        assert!(line.contains("\x1b[1;38;2;255;255;255mmonorepo\x1b[0m"), "{:?}", line);
+
+        // At the root, just the name
+        status.current_dir = "/Users/gak/src/monorepo".to_string();
+        assert_eq!(plain(&status.format_with(&config)), "monorepo");
+
+        // Wandered off: marked, and shown as usual
+        status.current_dir = "/tmp/scratch".to_string();
+        status.directory = "/tmp/scratch".to_string();
+        assert_eq!(plain(&status.format_with(&config)), "-> /tmp/scratch");
+
+        // No project reported
+        status.project_dir = String::new();
+        assert_eq!(plain(&status.format_with(&config)), "/tmp/scratch");
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::transcript::{TokenUsage, TranscriptUsage};
+
+/// Prices in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+    /// Writing to the prompt cache (5-minute TTL)
+    pub cache_write: f64,
+    pub cache_read: f64,
+}
+
+impl ModelPricing {
+    const fn new(input: f64, output: f64, cache_write: f64, cache_read: f64) -> Self {
+        Self {
+            input,
+            output,
+            cache_write,
+            cache_read,
+        }
+    }
+
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let per_token = |tokens: u64, price: f64| tokens as f64 * price / 1_000_000.0;
+        per_token(usage.input_tokens, self.input)
+            + per_token(usage.output_tokens, self.output)
+            + per_token(usage.cache_creation_input_tokens, self.cache_write)
+            + per_token(usage.cache_read_input_tokens, self.cache_read)
+    }
+}
+
+/// Published list prices, keyed by model id prefix. The longest matching
+/// prefix wins, so `claude-opus-4-5` isn't priced as `claude-opus-4`.
+const BUILT_IN_PRICING: &[(&str, ModelPricing)] = &[
+    ("claude-opus-4-5", ModelPricing::new(5.0, 25.0, 6.25, 0.50)),
+    ("claude-opus-4", ModelPricing::new(15.0, 75.0, 18.75, 1.50)),
+    ("claude-sonnet-4", ModelPricing::new(3.0, 15.0, 3.75, 0.30)),
+    ("claude-haiku-4-5", ModelPricing::new(1.0, 5.0, 1.25, 0.10)),
+    ("claude-3-7-sonnet", ModelPricing::new(3.0, 15.0, 3.75, 0.30)),
+    ("claude-3-5-sonnet", ModelPricing::new(3.0, 15.0, 3.75, 0.30)),
+    ("claude-3-5-haiku", ModelPricing::new(0.80, 4.0, 1.0, 0.08)),
+    ("claude-3-opus", ModelPricing::new(15.0, 75.0, 18.75, 1.50)),
+    ("claude-3-haiku", ModelPricing::new(0.25, 1.25, 0.30, 0.03)),
+];
+
+/// Looks up pricing for `model_id`, preferring user overrides (also matched
+/// by prefix) over the built-in table
+pub fn lookup(model_id: &str, overrides: &BTreeMap<String, ModelPricing>) -> Option<ModelPricing> {
+    // Extended-context variants like `claude-sonnet-4-5[1m]` share the base id
+    let model_id = model_id.split('[').next().unwrap_or(model_id);
+
+    let user = overrides.iter().map(|(prefix, pricing)| (prefix.as_str(), *pricing));
+    longest_match(model_id, user).or_else(|| longest_match(model_id, BUILT_IN_PRICING.iter().copied()))
+}
+
+fn longest_match<'a>(
+    model_id: &str,
+    entries: impl Iterator<Item = (&'a str, ModelPricing)>,
+) -> Option<ModelPricing> {
+    entries
+        .filter(|(prefix, _)| model_id.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, pricing)| pricing)
+}
+
+/// Estimated session spend in USD. Each message is priced by the model that
+/// answered it; messages that don't name one use `model_id`. Returns `None`
+/// when some usage can't be priced, including a named model that isn't in
+/// the table, rather than showing a number that's quietly too low. Models
+/// that used no tokens, like Claude Code's `<synthetic>`, cost nothing and
+/// need no price.
+pub fn session_cost(
+    usage: &TranscriptUsage,
+    model_id: &str,
+    overrides: &BTreeMap<String, ModelPricing>,
+) -> Option<f64> {
+    if usage.by_model.is_empty() {
+        return None;
+    }
+
+    usage
+        .by_model
+        .iter()
+        .filter(|(_, tokens)| !tokens.is_empty())
+        .map(|(model, tokens)| {
+            let model = if model.is_empty() { model_id } else { model };
+            let pricing = lookup(model, overrides)?;
+            Some(pricing.cost(tokens))
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(input: u64, output: u64, cache_read: u64, cache_creation: u64) -> TokenUsage {
+        TokenUsage {
+            input_tokens: input,
+            output_tokens: output,
+            cache_read_input_tokens: cache_read,
+            cache_creation_input_tokens: cache_creation,
+        }
+    }
+
+    fn usage_for(model: &str, tokens: TokenUsage) -> TranscriptUsage {
+        let mut usage = TranscriptUsage::default();
+        usage.by_model.insert(model.to_string(), tokens);
+        usage
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let none = BTreeMap::new();
+        assert_eq!(lookup("claude-opus-4-5-20251101", &none).unwrap().input, 5.0);
+        assert_eq!(lookup("claude-opus-4-1-20250805", &none).unwrap().input, 15.0);
+        assert_eq!(lookup("claude-sonnet-4-5-20250929[1m]", &none).unwrap().input, 3.0);
+        assert_eq!(lookup("gpt-4o", &none), None);
+    }
+
+    #[test]
+    fn test_overrides_take_precedence() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("claude-sonnet".to_string(), ModelPricing::new(2.0, 10.0, 2.5, 0.2));
+        assert_eq!(lookup("claude-sonnet-4-5", &overrides).unwrap().input, 2.0);
+        assert_eq!(lookup("claude-opus-4", &overrides).unwrap().input, 15.0);
+    }
+
+    #[test]
+    fn test_cost_prices_cache_separately() {
+        let pricing = ModelPricing::new(3.0, 15.0, 3.75, 0.30);
+        let cost = pricing.cost(&tokens(100_000, 50_000, 2_000_000, 200_000));
+        // 0.30 input + 0.75 output + 0.60 cache read + 0.75 cache write
+        assert!((cost - 2.40).abs() < 1e-9, "{}", cost);
+    }
+
+    #[test]
+    fn test_session_cost_per_model() {
+        let mut usage = usage_for("claude-sonnet-4-5-20250929", tokens(1_000_000, 0, 0, 0));
+        usage.by_model.insert("claude-haiku-4-5-20251001".to_string(), tokens(1_000_000, 0, 0, 0));
+        let cost = session_cost(&usage, "claude-sonnet-4-5-20250929", &BTreeMap::new()).unwrap();
+        assert!((cost - 4.0).abs() < 1e-9, "{}", cost);
+    }
+
+    #[test]
+    fn test_unknown_message_model_falls_back_to_session_model() {
+        let usage = usage_for("", tokens(0, 1_000_000, 0, 0));
+        let cost = session_cost(&usage, "claude-sonnet-4-5", &BTreeMap::new()).unwrap();
+        assert!((cost - 15.0).abs() < 1e-9, "{}", cost);
+    }
+
+    #[test]
+    fn test_unpriced_usage_gives_no_cost() {
+        let usage = usage_for("mystery-model", tokens(10, 10, 0, 0));
+        assert_eq!(session_cost(&usage, "mystery-model", &BTreeMap::new()), None);
+        // Not priced at the session model's rate either
+        assert_eq!(session_cost(&usage, "claude-sonnet-4-5", &BTreeMap::new()), None);
+        assert_eq!(session_cost(&TranscriptUsage::default(), "claude-sonnet-4", &BTreeMap::new()), None);
+    }
+
+    #[test]
+    fn test_synthetic_messages_need_no_price() {
+        let mut usage = TranscriptUsage::default();
+        usage.add_line(r#"{"type":"assistant","message":{"id":"msg_1","model":"claude-sonnet-4-5","usage":{"input_tokens":1000000,"output_tokens":0}}}"#);
+        usage.add_line(r#"{"type":"assistant","message":{"id":"msg_2","model":"<synthetic>","usage":{"input_tokens":0,"output_tokens":0,"cache_read_input_tokens":0,"cache_creation_input_tokens":0}}}"#);
+        assert!(usage.by_model.contains_key("<synthetic>"));
+
+        let cost = session_cost(&usage, "claude-sonnet-4-5", &BTreeMap::new()).unwrap();
+        assert!((cost - 3.0).abs() < 1e-9, "{}", cost);
+    }
+}
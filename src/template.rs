@@ -0,0 +1,380 @@
+use std::fmt;
+
+use crate::color::Rgb;
+
+/// Placeholder names a template may use. Segment names render the whole
+/// segment (icon and colors included); the rest render a single raw value.
+pub const VARIABLES: &[&str] = &[
+    // Segments
+    "directory",
+    "dir",
+    "vcs",
+    "model",
+    "output_style",
+    "style",
+    "context",
+    "ctx",
+    "cost",
+    // VCS fields
+    "revision",
+    "refs",
+    "backend",
+    // Claude Code input fields
+    "model_id",
+    "session_id",
+    "version",
+    "cwd",
+    "project_dir",
+    // Transcript token usage
+    "context_percent",
+    "context_tokens",
+    "input_tokens",
+    "output_tokens",
+    "cache_read_tokens",
+    "cache_creation_tokens",
+    "cost_usd",
+];
+
+/// Placeholders starting with this reach input fields this version doesn't
+/// know about by dotted path, e.g. `{extra.cost.total_cost_usd}`
+pub const EXTRA_PREFIX: &str = "extra.";
+
+/// Text attributes and color for a placeholder, e.g. `{model:bold #ff8c00}`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Style {
+    pub color: Option<Rgb>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub dimmed: bool,
+}
+
+impl Style {
+    fn parse(spec: &str) -> Result<Self, String> {
+        let mut style = Style::default();
+        for token in spec.split_whitespace() {
+            match token {
+                "bold" => style.bold = true,
+                "italic" => style.italic = true,
+                "underline" => style.underline = true,
+                "dimmed" | "dim" => style.dimmed = true,
+                _ if token.starts_with('#') => style.color = Some(token.parse()?),
+                _ => return Err(format!("unknown style `{}`", token)),
+            }
+        }
+        Ok(style)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(String),
+    Placeholder {
+        name: String,
+        optional: bool,
+        style: Style,
+    },
+    /// `( ... )`: shown only when a placeholder inside has a value
+    Section(Vec<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateError {
+    /// 1-based character position in the template
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "template error at column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// A statusline layout such as `"{dir} {vcs?} {model} {style?}"`.
+///
+/// - `{name}` is replaced by the named value, or nothing when it is empty
+/// - `{name?}` is optional: when empty, the literal text before it (or after
+///   it, when it comes first) is dropped too, so separators don't pile up
+/// - `{name:bold #ff8c00}` overrides the color and adds text attributes
+/// - `( ... )` is a conditional section, shown only when at least one
+///   placeholder inside it has a value
+/// - `\{`, `\}`, `\(`, `\)` and `\\` are literal characters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let chars: Vec<char> = source.chars().collect();
+        let mut pos = 0;
+        let nodes = parse_nodes(&chars, &mut pos, false)?;
+        Ok(Self { nodes })
+    }
+
+    /// Names of all placeholders, in order of appearance
+    pub fn placeholders(&self) -> Vec<&str> {
+        fn collect<'a>(nodes: &'a [Node], names: &mut Vec<&'a str>) {
+            for node in nodes {
+                match node {
+                    Node::Placeholder { name, .. } => names.push(name),
+                    Node::Section(inner) => collect(inner, names),
+                    Node::Literal(_) => {}
+                }
+            }
+        }
+
+        let mut names = Vec::new();
+        collect(&self.nodes, &mut names);
+        names
+    }
+
+    /// Fails on the first placeholder that isn't in `known` or an
+    /// `extra.*` field
+    pub fn check_names(&self, known: &[&str]) -> Result<(), String> {
+        let is_known = |name: &&str| {
+            known.contains(name) || name.strip_prefix(EXTRA_PREFIX).is_some_and(|path| !path.is_empty())
+        };
+        match self.placeholders().into_iter().find(|name| !is_known(name)) {
+            Some(name) => Err(format!(
+                "unknown placeholder `{{{}}}`, expected one of: {}",
+                name,
+                known.join(", ")
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Renders the template, asking `resolve` for each placeholder's value
+    pub fn render(&self, resolve: &dyn Fn(&str, &Style) -> Option<String>) -> String {
+        render_nodes(&self.nodes, resolve).0
+    }
+}
+
+fn parse_nodes(chars: &[char], pos: &mut usize, in_section: bool) -> Result<Vec<Node>, TemplateError> {
+    let mut nodes = Vec::new();
+    let mut literal = String::new();
+    let section_start = pos.saturating_sub(1);
+
+    while *pos < chars.len() {
+        let c = chars[*pos];
+        match c {
+            '\\' => {
+                match chars.get(*pos + 1) {
+                    Some(&escaped) if "{}()\\".contains(escaped) => literal.push(escaped),
+                    _ => return Err(error(*pos, "`\\` must be followed by one of `{ } ( ) \\`")),
+                }
+                *pos += 2;
+            }
+            '{' => {
+                flush(&mut literal, &mut nodes);
+                nodes.push(parse_placeholder(chars, pos)?);
+            }
+            '(' => {
+                flush(&mut literal, &mut nodes);
+                *pos += 1;
+                nodes.push(Node::Section(parse_nodes(chars, pos, true)?));
+            }
+            ')' if in_section => {
+                flush(&mut literal, &mut nodes);
+                *pos += 1;
+                return Ok(nodes);
+            }
+            ')' => return Err(error(*pos, "unmatched `)`")),
+            '}' => return Err(error(*pos, "unmatched `}`")),
+            _ => {
+                literal.push(c);
+                *pos += 1;
+            }
+        }
+    }
+
+    if in_section {
+        return Err(error(section_start, "unclosed `(`"));
+    }
+
+    flush(&mut literal, &mut nodes);
+    Ok(nodes)
+}
+
+fn parse_placeholder(chars: &[char], pos: &mut usize) -> Result<Node, TemplateError> {
+    let start = *pos;
+    let end = chars[start..]
+        .iter()
+        .position(|&c| c == '}')
+        .map(|offset| start + offset)
+        .ok_or_else(|| error(start, "unclosed `{`"))?;
+    *pos = end + 1;
+
+    let body: String = chars[start + 1..end].iter().collect();
+    let (spec, style) = match body.split_once(':') {
+        Some((spec, style)) => (spec, Style::parse(style).map_err(|message| error(start, &message))?),
+        None => (body.as_str(), Style::default()),
+    };
+    let (name, optional) = match spec.trim().strip_suffix('?') {
+        Some(name) => (name, true),
+        None => (spec.trim(), false),
+    };
+
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+        return Err(error(start, &format!("invalid placeholder name `{}`", name)));
+    }
+
+    Ok(Node::Placeholder {
+        name: name.to_string(),
+        optional,
+        style,
+    })
+}
+
+fn flush(literal: &mut String, nodes: &mut Vec<Node>) {
+    if !literal.is_empty() {
+        nodes.push(Node::Literal(std::mem::take(literal)));
+    }
+}
+
+fn error(pos: usize, message: &str) -> TemplateError {
+    TemplateError {
+        column: pos + 1,
+        message: message.to_string(),
+    }
+}
+
+/// Returns the rendered text and whether any placeholder produced a value
+fn render_nodes(nodes: &[Node], resolve: &dyn Fn(&str, &Style) -> Option<String>) -> (String, bool) {
+    // (text, is_literal) pieces, so empty optionals can drop their neighbours
+    let mut pieces: Vec<(String, bool)> = Vec::new();
+    let mut has_value = false;
+    let mut skip_next_literal = false;
+
+    for node in nodes {
+        match node {
+            Node::Literal(text) => {
+                if !std::mem::take(&mut skip_next_literal) {
+                    pieces.push((text.clone(), true));
+                }
+            }
+            Node::Placeholder { name, optional, style } => {
+                match resolve(name, style).filter(|value| !value.is_empty()) {
+                    Some(value) => {
+                        has_value = true;
+                        skip_next_literal = false;
+                        pieces.push((value, false));
+                    }
+                    None if *optional => {
+                        if pieces.last().is_some_and(|(_, is_literal)| *is_literal) {
+                            pieces.pop();
+                        } else {
+                            skip_next_literal = true;
+                        }
+                    }
+                    None => {}
+                }
+            }
+            Node::Section(inner) => {
+                let (text, inner_has_value) = render_nodes(inner, resolve);
+                if inner_has_value {
+                    has_value = true;
+                    skip_next_literal = false;
+                    pieces.push((text, false));
+                }
+            }
+        }
+    }
+
+    let text = pieces.into_iter().map(|(text, _)| text).collect();
+    (text, has_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, values: &[(&str, &str)]) -> String {
+        Template::parse(template).unwrap().render(&|name, _| {
+            values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn test_literal_and_placeholders() {
+        let values = [("dir", "~/src"), ("model", "Sonnet")];
+        assert_eq!(render("{dir} on {model}", &values), "~/src on Sonnet");
+    }
+
+    #[test]
+    fn test_optional_drops_preceding_separator() {
+        let values = [("dir", "~/src"), ("model", "Sonnet")];
+        assert_eq!(render("{dir} | {vcs?} | {model} | {style?}", &values), "~/src | Sonnet");
+    }
+
+    #[test]
+    fn test_leading_optional_drops_following_separator() {
+        let values = [("model", "Sonnet")];
+        assert_eq!(render("{vcs?} | {model}", &values), "Sonnet");
+    }
+
+    #[test]
+    fn test_optional_with_value_is_kept() {
+        let values = [("dir", "~/src"), ("vcs", "abc123"), ("model", "Sonnet")];
+        assert_eq!(render("{dir} {vcs?} {model}", &values), "~/src abc123 Sonnet");
+    }
+
+    #[test]
+    fn test_conditional_section() {
+        let template = "{model}( [{style}])";
+        assert_eq!(render(template, &[("model", "Sonnet")]), "Sonnet");
+        assert_eq!(
+            render(template, &[("model", "Sonnet"), ("style", "Learning")]),
+            "Sonnet [Learning]"
+        );
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(render(r"\{{model}\} \(x\) \\", &[("model", "m")]), r"{m} (x) \");
+    }
+
+    #[test]
+    fn test_placeholder_style() {
+        let template = Template::parse("{model:bold #ff8c00}").unwrap();
+        let rendered = template.render(&|_, style| {
+            Some(format!("{:?} {}", style.color.map(|c| c.to_string()), style.bold))
+        });
+        assert_eq!(rendered, r##"Some("#ff8c00") true"##);
+    }
+
+    #[test]
+    fn test_placeholders_and_name_check() {
+        let template = Template::parse("{dir} ({vcs?} {weather})").unwrap();
+        assert_eq!(template.placeholders(), vec!["dir", "vcs", "weather"]);
+        let error = template.check_names(VARIABLES).unwrap_err();
+        assert!(error.starts_with("unknown placeholder `{weather}`"), "{}", error);
+
+        assert!(Template::parse("{extra.cost.total_cost_usd}").unwrap().check_names(VARIABLES).is_ok());
+        assert!(Template::parse("{extra.}").unwrap().check_names(VARIABLES).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("{dir", 1, "unclosed `{`"),
+            ("{dir} (x", 7, "unclosed `(`"),
+            ("x)", 2, "unmatched `)`"),
+            ("x}", 2, "unmatched `}`"),
+            ("{}", 1, "invalid placeholder name ``"),
+            ("{model:sparkly}", 1, "unknown style `sparkly`"),
+            (r"\x", 1, "`\\` must be followed by one of `{ } ( ) \\`"),
+        ];
+        for (source, column, message) in cases {
+            let error = Template::parse(source).unwrap_err();
+            assert_eq!((error.column, error.message.as_str()), (column, message), "{}", source);
+        }
+    }
+}
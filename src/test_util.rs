@@ -0,0 +1,7 @@
+/// An environment holding just `vars`, for testing lookups like
+/// [`ColorMode::resolve`](crate::config::ColorMode::resolve)'s without
+/// touching the real one
+pub fn fake_env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: Vec<(String, String)> = vars.iter().map(|(var, value)| (var.to_string(), value.to_string())).collect();
+    move |name| vars.iter().find(|(var, _)| var == name).map(|(_, value)| value.clone())
+}
@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::color::Color;
+use crate::config::default_config_path;
+
+/// The colors a statusline is drawn with. Segment colors set in the config
+/// still win over the theme's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub separator: Color,
+    pub directory: Color,
+    /// Revision, conflict and ahead/behind
+    pub vcs: Color,
+    /// Bookmarks and branch names
+    pub vcs_ref: Color,
+    pub model: Color,
+    pub output_style: Color,
+    pub context: Color,
+    /// Context once the window is nearly full
+    pub context_warn: Color,
+    pub cost: Color,
+    /// The marker shown when the line is degraded
+    pub warning: Color,
+    /// Text on segment backgrounds in the powerline and rounded styles
+    pub text: Color,
+}
+
+/// The original look: punchy 24-bit colors
+pub const VIBRANT: Theme = Theme {
+    separator: Color::rgb(96, 96, 96),       // Dark grey
+    directory: Color::rgb(64, 224, 208),     // Turquoise with punch
+    vcs: Color::rgb(200, 80, 140),           // Duller hot pink with more grey
+    vcs_ref: Color::rgb(255, 20, 147),       // Full hot pink for branch names
+    model: Color::rgb(255, 140, 0),          // Vibrant orange
+    output_style: Color::rgb(50, 205, 50),   // Lime green
+    context: Color::rgb(100, 149, 237),      // Cornflower blue
+    context_warn: Color::rgb(255, 69, 0),    // Orange red
+    cost: Color::rgb(255, 215, 0),           // Gold
+    warning: Color::rgb(255, 59, 48),        // Alarm red
+    text: Color::rgb(28, 28, 28),            // Near black
+};
+
+/// Solarized dark accents. At 16 colors they map onto the ANSI slots a
+/// Solarized terminal palette puts them in.
+pub const SOLARIZED: Theme = Theme {
+    separator: Color::rgb(0x58, 0x6e, 0x75).with_ansi16(10),    // base01
+    directory: Color::rgb(0x2a, 0xa1, 0x98).with_ansi16(6),     // cyan
+    vcs: Color::rgb(0x6c, 0x71, 0xc4).with_ansi16(13),          // violet
+    vcs_ref: Color::rgb(0xd3, 0x36, 0x82).with_ansi16(5),       // magenta
+    model: Color::rgb(0xcb, 0x4b, 0x16).with_ansi16(9),         // orange
+    output_style: Color::rgb(0x85, 0x99, 0x00).with_ansi16(2),  // green
+    context: Color::rgb(0x26, 0x8b, 0xd2).with_ansi16(4),       // blue
+    context_warn: Color::rgb(0xdc, 0x32, 0x2f).with_ansi16(1),  // red
+    cost: Color::rgb(0xb5, 0x89, 0x00).with_ansi16(3),          // yellow
+    warning: Color::rgb(0xdc, 0x32, 0x2f).with_ansi16(1),       // red
+    text: Color::rgb(0x00, 0x2b, 0x36).with_ansi16(8),          // base03
+};
+
+/// Gruvbox dark
+pub const GRUVBOX: Theme = Theme {
+    separator: Color::rgb(0x92, 0x83, 0x74),    // gray
+    directory: Color::rgb(0x8e, 0xc0, 0x7c),    // aqua
+    vcs: Color::rgb(0xb1, 0x62, 0x86),          // purple
+    vcs_ref: Color::rgb(0xd3, 0x86, 0x9b),      // bright purple
+    model: Color::rgb(0xfe, 0x80, 0x19),        // orange
+    output_style: Color::rgb(0xb8, 0xbb, 0x26), // green
+    context: Color::rgb(0x83, 0xa5, 0x98),      // blue
+    context_warn: Color::rgb(0xfb, 0x49, 0x34), // red
+    cost: Color::rgb(0xfa, 0xbd, 0x2f),         // yellow
+    warning: Color::rgb(0xfb, 0x49, 0x34),      // red
+    text: Color::rgb(0x28, 0x28, 0x28),         // bg
+};
+
+/// Catppuccin Mocha
+pub const CATPPUCCIN: Theme = Theme {
+    separator: Color::rgb(0x6c, 0x70, 0x86),    // overlay0
+    directory: Color::rgb(0x94, 0xe2, 0xd5),    // teal
+    vcs: Color::rgb(0xcb, 0xa6, 0xf7),          // mauve
+    vcs_ref: Color::rgb(0xf5, 0xc2, 0xe7),      // pink
+    model: Color::rgb(0xfa, 0xb3, 0x87),        // peach
+    output_style: Color::rgb(0xa6, 0xe3, 0xa1), // green
+    context: Color::rgb(0x89, 0xb4, 0xfa),      // blue
+    context_warn: Color::rgb(0xf3, 0x8b, 0xa8), // red
+    cost: Color::rgb(0xf9, 0xe2, 0xaf),         // yellow
+    warning: Color::rgb(0xf3, 0x8b, 0xa8),      // red
+    text: Color::rgb(0x11, 0x11, 0x1b),         // crust
+};
+
+/// Fully saturated colors on the bright ANSI slots, readable on a dark
+/// background at any color depth
+pub const HIGH_CONTRAST: Theme = Theme {
+    separator: Color::rgb(255, 255, 255).with_ansi16(15),
+    directory: Color::rgb(0, 255, 255).with_ansi16(14),
+    vcs: Color::rgb(255, 255, 255).with_ansi16(15),
+    vcs_ref: Color::rgb(255, 255, 0).with_ansi16(11),
+    model: Color::rgb(0, 255, 0).with_ansi16(10),
+    output_style: Color::rgb(255, 0, 255).with_ansi16(13),
+    context: Color::rgb(255, 255, 255).with_ansi16(15),
+    context_warn: Color::rgb(255, 0, 0).with_ansi16(9),
+    cost: Color::rgb(255, 255, 0).with_ansi16(11),
+    warning: Color::rgb(255, 0, 0).with_ansi16(9),
+    text: Color::rgb(0, 0, 0).with_ansi16(0),
+};
+
+pub const BUILT_IN: &[(&str, Theme)] = &[
+    ("vibrant", VIBRANT),
+    ("solarized", SOLARIZED),
+    ("gruvbox", GRUVBOX),
+    ("catppuccin", CATPPUCCIN),
+    ("high-contrast", HIGH_CONTRAST),
+];
+
+impl Default for Theme {
+    fn default() -> Self {
+        VIBRANT
+    }
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Unknown(String),
+    Io { path: PathBuf, source: std::io::Error },
+    Parse { path: PathBuf, message: String },
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::Unknown(name) => {
+                let names: Vec<&str> = BUILT_IN.iter().map(|(name, _)| *name).collect();
+                write!(
+                    f,
+                    "unknown theme `{}`: expected one of {} or a theme file",
+                    name,
+                    names.join(", ")
+                )
+            }
+            ThemeError::Io { path, source } => {
+                write!(f, "cannot read theme {}: {}", path.display(), source)
+            }
+            ThemeError::Parse { path, message } => {
+                write!(f, "invalid theme {}: {}", path.display(), message.trim_end())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {}
+
+impl Theme {
+    /// A built-in theme by name, `themes/<name>.toml` next to the config
+    /// file at `config_path` (the default one when `None`), or a theme file
+    /// when `name` is a path (contains `/`)
+    pub fn load(name: &str, config_path: Option<&Path>) -> Result<Self, ThemeError> {
+        if let Some((_, theme)) = BUILT_IN.iter().find(|(built_in, _)| *built_in == name) {
+            return Ok(*theme);
+        }
+
+        if name.contains('/') {
+            return Self::from_file(Path::new(name));
+        }
+        match themes_dir(config_path).map(|dir| dir.join(format!("{}.toml", name))) {
+            Some(path) if path.exists() => Self::from_file(&path),
+            _ => Err(ThemeError::Unknown(name.to_string())),
+        }
+    }
+
+    /// Reads a theme file. Colors it leaves out come from `vibrant`.
+    pub fn from_file(path: &Path) -> Result<Self, ThemeError> {
+        let text = fs::read_to_string(path).map_err(|source| ThemeError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|e| ThemeError::Parse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })
+    }
+}
+
+/// `themes` next to the config file
+fn themes_dir(config_path: Option<&Path>) -> Option<PathBuf> {
+    let config_path = config_path.map(Path::to_path_buf).or_else(default_config_path)?;
+    Some(config_path.parent()?.join("themes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Rgb;
+
+    #[test]
+    fn test_built_in_themes() {
+        assert_eq!(Theme::load("vibrant", None).unwrap(), Theme::default());
+        assert_eq!(Theme::load("gruvbox", None).unwrap().directory.rgb, Rgb::new(0x8e, 0xc0, 0x7c));
+        for (name, _) in BUILT_IN {
+            assert!(Theme::load(name, None).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_unknown_theme() {
+        let error = Theme::load("dracula-nonexistent", None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown theme `dracula-nonexistent`: expected one of vibrant, solarized, gruvbox, catppuccin, \
+             high-contrast or a theme file"
+        );
+    }
+
+    #[test]
+    fn test_theme_file_fills_in_from_vibrant() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mine.toml");
+        fs::write(
+            &path,
+            r##"
+            directory = "#112233"
+            vcs_ref = { rgb = "#ff1493", ansi16 = "bright_magenta" }
+            "##,
+        )
+        .unwrap();
+
+        let theme = Theme::load(path.to_str().unwrap(), None).unwrap();
+        assert_eq!(theme.directory, Color::rgb(0x11, 0x22, 0x33));
+        assert_eq!(theme.vcs_ref.ansi16, Some(13));
+        assert_eq!(theme.model, VIBRANT.model);
+    }
+
+    #[test]
+    fn test_theme_next_to_config() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("themes")).unwrap();
+        fs::write(dir.path().join("themes/dusk.toml"), "directory = \"#112233\"").unwrap();
+
+        let theme = Theme::load("dusk", Some(&dir.path().join("config.toml"))).unwrap();
+        assert_eq!(theme.directory, Color::rgb(0x11, 0x22, 0x33));
+    }
+
+    #[test]
+    fn test_bad_theme_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.toml");
+        fs::write(&path, "directroy = \"#112233\"").unwrap();
+
+        let error = Theme::load(path.to_str().unwrap(), None).unwrap_err().to_string();
+        assert!(error.starts_with("invalid theme"), "{}", error);
+        assert!(error.contains("unknown field `directroy`"), "{}", error);
+
+        let error = Theme::load("/nonexistent/theme.toml", None).unwrap_err().to_string();
+        assert!(error.starts_with("cannot read theme /nonexistent/theme.toml"), "{}", error);
+    }
+}
@@ -0,0 +1,387 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::cache;
+
+/// Context window size for models without a known larger one
+const DEFAULT_CONTEXT_WINDOW: u64 = 200_000;
+const EXTENDED_CONTEXT_WINDOW: u64 = 1_000_000;
+
+/// Token counts as reported in an assistant message's `usage`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_input_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+    }
+
+    /// No tokens at all, as on the `<synthetic>` messages Claude Code writes
+    /// itself
+    pub fn is_empty(&self) -> bool {
+        *self == TokenUsage::default()
+    }
+
+    /// Tokens the request sent to the model, i.e. how much of the context
+    /// window the conversation occupied at that point
+    pub fn context_tokens(&self) -> u64 {
+        self.input_tokens + self.cache_read_input_tokens + self.cache_creation_input_tokens
+    }
+}
+
+/// Token usage accumulated over a whole transcript
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TranscriptUsage {
+    /// Sum over every assistant message, subagents included
+    pub total: TokenUsage,
+    /// The same sum split by the model that answered, for pricing. Messages
+    /// without a model are filed under `""`.
+    pub by_model: BTreeMap<String, TokenUsage>,
+    /// Usage of the latest main-conversation assistant message
+    pub last: Option<TokenUsage>,
+    /// Id of the last assistant message counted. Claude Code writes one line
+    /// per content block, each repeating the message's usage.
+    pub last_message_id: Option<String>,
+}
+
+impl TranscriptUsage {
+    /// Adds one JSONL line. Lines that aren't assistant messages with usage,
+    /// or that aren't valid JSON (e.g. a half-written last line), are ignored.
+    pub fn add_line(&mut self, line: &str) {
+        let entry: TranscriptEntry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
+            Err(_) => return,
+        };
+
+        let message = match entry.message {
+            Some(message) if entry.entry_type == "assistant" => message,
+            _ => return,
+        };
+        let usage = match message.usage {
+            Some(usage) => usage,
+            None => return,
+        };
+
+        if message.id.is_some() && message.id == self.last_message_id {
+            return;
+        }
+        self.last_message_id = message.id;
+
+        self.total.add(&usage);
+        self.by_model
+            .entry(message.model.unwrap_or_default())
+            .or_default()
+            .add(&usage);
+        if !entry.is_sidechain {
+            self.last = Some(usage);
+        }
+    }
+
+    /// How full the context window is, sized for `model_id`
+    pub fn context(&self, model_id: &str) -> Option<ContextUsage> {
+        let last = self.last?;
+        Some(ContextUsage {
+            used: last.context_tokens(),
+            window: context_window(model_id),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ContextUsage {
+    pub used: u64,
+    pub window: u64,
+}
+
+impl ContextUsage {
+    pub fn percent(&self) -> u64 {
+        if self.window == 0 {
+            return 0;
+        }
+        (self.used * 100 / self.window).min(100)
+    }
+}
+
+#[derive(Deserialize)]
+struct TranscriptEntry {
+    #[serde(rename = "type", default)]
+    entry_type: String,
+    #[serde(rename = "isSidechain", default)]
+    is_sidechain: bool,
+    message: Option<TranscriptMessage>,
+}
+
+#[derive(Deserialize)]
+struct TranscriptMessage {
+    id: Option<String>,
+    model: Option<String>,
+    usage: Option<TokenUsage>,
+}
+
+/// Context window size for a Claude Code model id. Extended-context variants
+/// carry a `[1m]` suffix, e.g. `claude-sonnet-4-5[1m]`.
+pub fn context_window(model_id: &str) -> u64 {
+    if model_id.to_ascii_lowercase().ends_with("[1m]") {
+        EXTENDED_CONTEXT_WINDOW
+    } else {
+        DEFAULT_CONTEXT_WINDOW
+    }
+}
+
+/// Streams a JSONL transcript and totals its token usage
+pub fn read_usage(path: &Path) -> io::Result<TranscriptUsage> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut usage = TranscriptUsage::default();
+
+    for line in reader.lines() {
+        usage.add_line(&line?);
+    }
+
+    Ok(usage)
+}
+
+/// Running totals for a transcript up to `offset`, saved between runs so
+/// each redraw only parses lines appended since the last one
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct UsageCache {
+    path: PathBuf,
+    inode: u64,
+    offset: u64,
+    usage: TranscriptUsage,
+}
+
+/// Like [`read_usage`], but resumes from the totals cached for `session_id`
+/// and only parses what was appended since. A transcript that shrank or was
+/// replaced (new inode) is read again from the start.
+pub fn read_usage_cached(path: &Path, session_id: &str, cache_dir: &Path) -> io::Result<TranscriptUsage> {
+    let cache_path = cache_dir.join("transcripts").join(cache::file_name(session_id, "json"));
+    let mut file = File::open(path)?;
+    let metadata = file.metadata()?;
+    let inode = inode(&metadata);
+
+    let mut cache = fs::read(&cache_path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<UsageCache>(&bytes).ok())
+        .filter(|cache| cache.path == path && cache.inode == inode && cache.offset <= metadata.len())
+        .unwrap_or_else(|| UsageCache {
+            path: path.to_path_buf(),
+            inode,
+            ..Default::default()
+        });
+
+    if cache.offset == metadata.len() {
+        return Ok(cache.usage);
+    }
+
+    file.seek(SeekFrom::Start(cache.offset))?;
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        // Stop before a line that is still being written, so it's parsed
+        // whole on the next run instead of being skipped as invalid JSON
+        if read == 0 || !line.ends_with('\n') {
+            break;
+        }
+        cache.usage.add_line(&line);
+        cache.offset += read as u64;
+    }
+
+    // The totals are right either way; a failed write only costs a reparse
+    if let Ok(bytes) = serde_json::to_vec(&cache) {
+        let _ = cache::write_atomic(&cache_path, &bytes);
+    }
+
+    Ok(cache.usage)
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(metadata)
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assistant(id: &str, input: u64, output: u64, cache_read: u64, cache_creation: u64) -> String {
+        format!(
+            r#"{{"type":"assistant","message":{{"id":"{}","role":"assistant","usage":{{"input_tokens":{},"output_tokens":{},"cache_read_input_tokens":{},"cache_creation_input_tokens":{}}}}}}}"#,
+            id, input, output, cache_read, cache_creation
+        )
+    }
+
+    #[test]
+    fn test_sums_assistant_usage() {
+        let mut usage = TranscriptUsage::default();
+        usage.add_line(r#"{"type":"user","message":{"role":"user","content":"hi"}}"#);
+        usage.add_line(&assistant("msg_1", 10, 20, 100, 50));
+        usage.add_line(&assistant("msg_2", 5, 30, 150, 10));
+
+        assert_eq!(
+            usage.total,
+            TokenUsage {
+                input_tokens: 15,
+                output_tokens: 50,
+                cache_read_input_tokens: 250,
+                cache_creation_input_tokens: 60,
+            }
+        );
+        assert_eq!(usage.last.map(|last| last.context_tokens()), Some(165));
+    }
+
+    #[test]
+    fn test_usage_by_model() {
+        let mut usage = TranscriptUsage::default();
+        let with_model = |id, model| {
+            assistant(id, 1, 2, 0, 0).replacen(r#""role""#, &format!(r#""model":"{}","role""#, model), 1)
+        };
+        usage.add_line(&with_model("msg_1", "claude-sonnet-4-5"));
+        usage.add_line(&with_model("msg_2", "claude-haiku-4-5"));
+        usage.add_line(&with_model("msg_3", "claude-sonnet-4-5"));
+        usage.add_line(&assistant("msg_4", 1, 2, 0, 0));
+
+        assert_eq!(usage.by_model["claude-sonnet-4-5"].output_tokens, 4);
+        assert_eq!(usage.by_model["claude-haiku-4-5"].output_tokens, 2);
+        assert_eq!(usage.by_model[""].output_tokens, 2);
+    }
+
+    #[test]
+    fn test_repeated_message_lines_count_once() {
+        let mut usage = TranscriptUsage::default();
+        usage.add_line(&assistant("msg_1", 10, 20, 0, 0));
+        usage.add_line(&assistant("msg_1", 10, 20, 0, 0));
+        assert_eq!(usage.total.output_tokens, 20);
+    }
+
+    #[test]
+    fn test_sidechain_counts_toward_total_not_context() {
+        let mut usage = TranscriptUsage::default();
+        usage.add_line(&assistant("msg_1", 10, 20, 1000, 0));
+        let sidechain = assistant("msg_2", 5, 5, 0, 0).replacen('{', r#"{"isSidechain":true,"#, 1);
+        usage.add_line(&sidechain);
+
+        assert_eq!(usage.total.input_tokens, 15);
+        assert_eq!(usage.last.map(|last| last.context_tokens()), Some(1010));
+    }
+
+    #[test]
+    fn test_ignores_malformed_lines() {
+        let mut usage = TranscriptUsage::default();
+        usage.add_line("");
+        usage.add_line("{not json");
+        usage.add_line(r#"{"type":"assistant","message":{"content":[]}}"#);
+        assert_eq!(usage, TranscriptUsage::default());
+    }
+
+    #[test]
+    fn test_context_percent() {
+        let mut usage = TranscriptUsage::default();
+        usage.add_line(&assistant("msg_1", 4_000, 500, 100_000, 20_000));
+
+        let context = usage.context("claude-sonnet-4-5-20250929").unwrap();
+        assert_eq!(context.window, 200_000);
+        assert_eq!(context.percent(), 62);
+
+        let context = usage.context("claude-sonnet-4-5-20250929[1m]").unwrap();
+        assert_eq!(context.window, 1_000_000);
+        assert_eq!(context.percent(), 12);
+    }
+
+    #[test]
+    fn test_no_context_without_assistant_messages() {
+        assert_eq!(TranscriptUsage::default().context("claude-sonnet-4-5"), None);
+    }
+
+    #[test]
+    fn test_read_usage_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript.jsonl");
+        let lines = [assistant("msg_1", 1, 2, 3, 4), assistant("msg_2", 1, 2, 3, 4)];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let usage = read_usage(&path).unwrap();
+        assert_eq!(usage.total.output_tokens, 4);
+        assert_eq!(usage.last_message_id.as_deref(), Some("msg_2"));
+    }
+
+    #[test]
+    fn test_cached_read_only_parses_appended_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript.jsonl");
+        let cache_dir = dir.path().join("cache");
+        std::fs::write(&path, assistant("msg_1", 1, 2, 3, 4) + "\n").unwrap();
+
+        let usage = read_usage_cached(&path, "session", &cache_dir).unwrap();
+        assert_eq!(usage.total.output_tokens, 2);
+
+        // A cached total that differs from the file proves the prefix isn't reparsed
+        let cache_path = cache_dir.join("transcripts").join("session.json");
+        let mut cache: UsageCache = serde_json::from_slice(&std::fs::read(&cache_path).unwrap()).unwrap();
+        cache.usage.total.output_tokens = 100;
+        std::fs::write(&cache_path, serde_json::to_vec(&cache).unwrap()).unwrap();
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        std::io::Write::write_all(&mut file, (assistant("msg_2", 1, 2, 3, 4) + "\n").as_bytes()).unwrap();
+
+        let usage = read_usage_cached(&path, "session", &cache_dir).unwrap();
+        assert_eq!(usage.total.output_tokens, 102);
+        assert_eq!(usage.last_message_id.as_deref(), Some("msg_2"));
+    }
+
+    #[test]
+    fn test_cached_read_waits_for_complete_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript.jsonl");
+        let cache_dir = dir.path().join("cache");
+        let line = assistant("msg_1", 1, 2, 3, 4);
+        let (head, tail) = line.split_at(line.len() / 2);
+
+        std::fs::write(&path, head).unwrap();
+        assert_eq!(read_usage_cached(&path, "session", &cache_dir).unwrap().total.output_tokens, 0);
+
+        std::fs::write(&path, format!("{}{}\n", head, tail)).unwrap();
+        assert_eq!(read_usage_cached(&path, "session", &cache_dir).unwrap().total.output_tokens, 2);
+    }
+
+    #[test]
+    fn test_cached_read_rebuilds_after_truncation_or_replacement() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript.jsonl");
+        let cache_dir = dir.path().join("cache");
+        let lines = |count: usize| -> String {
+            (0..count).map(|i| assistant(&format!("msg_{}", i), 1, 2, 3, 4) + "\n").collect()
+        };
+
+        std::fs::write(&path, lines(3)).unwrap();
+        assert_eq!(read_usage_cached(&path, "session", &cache_dir).unwrap().total.output_tokens, 6);
+
+        // Shrunk in place
+        std::fs::write(&path, lines(1)).unwrap();
+        assert_eq!(read_usage_cached(&path, "session", &cache_dir).unwrap().total.output_tokens, 2);
+
+        // Rotated: a new file of the same size or larger under the same name
+        let rotated = dir.path().join("rotated.jsonl");
+        std::fs::write(&rotated, lines(2)).unwrap();
+        std::fs::rename(&rotated, &path).unwrap();
+        assert_eq!(read_usage_cached(&path, "session", &cache_dir).unwrap().total.output_tokens, 4);
+    }
+}
@@ -0,0 +1,441 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::deadline::{run_until, Cancel};
+use crate::git_status::GitProvider;
+use crate::jj_status::JjProvider;
+use crate::vcs_cache::{self, VcsCache};
+
+/// Shown in place of the revision when status didn't arrive in time and
+/// nothing older is known
+pub const PENDING: &str = "…";
+
+/// Backend-neutral repository status, as rendered by the VCS segment
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct VcsStatus {
+    /// Short name of the backend that produced this status, e.g. `jj` or `git`
+    pub backend: String,
+    /// Short id of the current revision (jj change id, git commit SHA)
+    pub revision: String,
+    /// Named refs pointing at the revision (jj bookmarks, git branch)
+    pub refs: Vec<String>,
+    /// In-progress multi-step operation such as `rebase` or `merge`
+    pub operation: Option<String>,
+    pub conflict: bool,
+    /// Uncommitted changes to tracked files
+    pub dirty: bool,
+    /// Changes staged for the next commit, for backends with an index
+    pub staged: bool,
+    /// Files not yet tracked, for backends that don't track automatically
+    pub untracked: bool,
+    pub ahead: usize,
+    pub behind: usize,
+    /// Root of the repository, for `{repo}` in link templates
+    #[serde(default)]
+    pub root: Option<PathBuf>,
+    /// Collection ran out of time and this is the last status seen, if any
+    #[serde(skip)]
+    pub stale: bool,
+}
+
+impl VcsStatus {
+    pub fn new(backend: &str, revision: &str) -> Self {
+        Self {
+            backend: backend.to_string(),
+            revision: revision.to_string(),
+            refs: Vec::new(),
+            operation: None,
+            conflict: false,
+            dirty: false,
+            staged: false,
+            untracked: false,
+            ahead: 0,
+            behind: 0,
+            root: None,
+            stale: false,
+        }
+    }
+
+    /// Placeholder for a repository whose status didn't arrive in time
+    pub fn pending(backend: &str) -> Self {
+        Self {
+            stale: true,
+            ..Self::new(backend, PENDING)
+        }
+    }
+
+    pub fn with_root(self, root: &Path) -> Self {
+        Self {
+            root: Some(root.to_path_buf()),
+            ..self
+        }
+    }
+
+    /// Name of the repository's root directory
+    pub fn repo_name(&self) -> Option<String> {
+        Some(self.root.as_ref()?.file_name()?.to_string_lossy().into_owned())
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.dirty || self.staged || self.untracked
+    }
+
+    /// Ahead/behind counts against upstream, e.g. `↑2↓1`; `None` when in sync
+    pub fn divergence(&self) -> Option<String> {
+        let mut result = String::new();
+        if self.ahead > 0 {
+            result.push_str(&format!("↑{}", self.ahead));
+        }
+        if self.behind > 0 {
+            result.push_str(&format!("↓{}", self.behind));
+        }
+        if result.is_empty() {
+            None
+        } else {
+            Some(result)
+        }
+    }
+
+    /// Change markers: `+` staged, `*` dirty, `?` untracked
+    pub fn markers(&self) -> String {
+        let mut result = String::new();
+        if self.staged {
+            result.push('+');
+        }
+        if self.dirty {
+            result.push('*');
+        }
+        if self.untracked {
+            result.push('?');
+        }
+        result
+    }
+
+    pub fn format(&self) -> String {
+        let mut parts = vec![self.revision.clone()];
+
+        if !self.refs.is_empty() {
+            parts.push(self.refs.join(" "));
+        }
+
+        if let Some(divergence) = self.divergence() {
+            parts.push(divergence);
+        }
+
+        if let Some(operation) = &self.operation {
+            parts.push(operation.clone());
+        }
+
+        if self.conflict {
+            parts.push("conflict".to_string());
+        }
+
+        let mut result = parts.join(" ");
+        result.push_str(&self.markers());
+
+        if self.stale && self.revision != PENDING {
+            result.push_str(&format!(" {}", PENDING));
+        }
+
+        result
+    }
+}
+
+/// A version control backend the statusline can report on
+pub trait VcsProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns the root of the repository containing `path`, if this backend
+    /// manages one. This should be cheap: it runs for every backend on every
+    /// redraw.
+    fn detect(&self, path: &Path) -> Option<PathBuf>;
+
+    /// Collects the status of the repository containing `path`. Child
+    /// processes go through [`output_until`](crate::deadline::output_until)
+    /// with `cancel`, so they're killed at `deadline` or when the caller
+    /// gives up waiting, whichever comes first.
+    fn status(&self, path: &Path, deadline: Instant, cancel: &Cancel) -> Option<VcsStatus>;
+}
+
+/// All known backends, in order of preference for colocated repositories
+pub fn default_providers() -> Vec<Arc<dyn VcsProvider>> {
+    vec![Arc::new(JjProvider), Arc::new(GitProvider)]
+}
+
+/// Picks the backend for `path`: the one whose repository root is nearest
+/// wins, so a git submodule inside a jj repo reports git and vice versa. When
+/// roots coincide (colocated jj+git) the earlier provider wins. Returns the
+/// provider along with the repository root.
+pub fn select_provider(
+    providers: &[Arc<dyn VcsProvider>],
+    path: &Path,
+) -> Option<(Arc<dyn VcsProvider>, PathBuf)> {
+    let mut best: Option<(&Arc<dyn VcsProvider>, PathBuf)> = None;
+
+    for provider in providers {
+        if let Some(root) = provider.detect(path) {
+            let depth = root.components().count();
+            if best.as_ref().is_none_or(|(_, best_root)| depth > best_root.components().count()) {
+                best = Some((provider, root));
+            }
+        }
+    }
+
+    best.map(|(provider, root)| (Arc::clone(provider), root))
+}
+
+/// VCS status for `path`, given up on at `deadline` so a stuck `jj` or a
+/// slow snapshot can't hold up the statusline. With a `cache`, a recent
+/// status is reused without probing while the repository looks unchanged,
+/// and the last one seen is shown, marked stale, when collection runs out of
+/// time; without one the revision is a [`PENDING`] placeholder.
+pub fn get_vcs_status(path: &str, deadline: Instant, cache: Option<&VcsCache>) -> Option<VcsStatus> {
+    status_from(&default_providers(), path, deadline, cache)
+}
+
+fn status_from(
+    providers: &[Arc<dyn VcsProvider>],
+    path: &str,
+    deadline: Instant,
+    cache: Option<&VcsCache>,
+) -> Option<VcsStatus> {
+    let (provider, root) = select_provider(providers, Path::new(path))?;
+
+    // Taken before probing, so changes made meanwhile invalidate the entry
+    let stamps = vcs_cache::stamps(&root);
+    if let Some(status) = cache.and_then(|cache| cache.fresh(&root, &stamps)) {
+        return Some(status);
+    }
+
+    let path = PathBuf::from(path);
+    let backend = provider.name();
+    match run_until(deadline, move |cancel| provider.status(&path, deadline, cancel)) {
+        Some(status) => {
+            let status = status.map(|status| status.with_root(&root));
+            if let (Some(status), Some(cache)) = (&status, cache) {
+                cache.store(&root, status, stamps);
+            }
+            status
+        }
+        None => {
+            let stale = cache
+                .and_then(|cache| cache.last_known(&root))
+                .map(|status| VcsStatus { stale: true, ..status });
+            Some(stale.unwrap_or_else(|| VcsStatus::pending(backend).with_root(&root)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_lib::config::StackedConfig;
+    use jj_lib::settings::UserSettings;
+    use jj_lib::workspace::Workspace;
+    use std::time::Duration;
+
+    struct FakeProvider {
+        name: &'static str,
+        root: Option<&'static str>,
+    }
+
+    impl VcsProvider for FakeProvider {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn detect(&self, _path: &Path) -> Option<PathBuf> {
+            self.root.map(PathBuf::from)
+        }
+
+        fn status(&self, _path: &Path, _deadline: Instant, _cancel: &Cancel) -> Option<VcsStatus> {
+            Some(VcsStatus::new(self.name, "abc123"))
+        }
+    }
+
+    /// Never finishes in time, like a jj snapshot of a huge working copy
+    struct HangingProvider {
+        root: PathBuf,
+    }
+
+    impl VcsProvider for HangingProvider {
+        fn name(&self) -> &'static str {
+            "git"
+        }
+
+        fn detect(&self, _path: &Path) -> Option<PathBuf> {
+            Some(self.root.clone())
+        }
+
+        fn status(&self, _path: &Path, _deadline: Instant, _cancel: &Cancel) -> Option<VcsStatus> {
+            std::thread::sleep(Duration::from_secs(5));
+            None
+        }
+    }
+
+    fn providers(roots: [Option<&'static str>; 2]) -> Vec<Arc<dyn VcsProvider>> {
+        vec![
+            Arc::new(FakeProvider { name: "jj", root: roots[0] }),
+            Arc::new(FakeProvider { name: "git", root: roots[1] }),
+        ]
+    }
+
+    #[test]
+    fn test_colocated_prefers_first_provider() {
+        let providers = providers([Some("/src/repo"), Some("/src/repo")]);
+        let selected = select_provider(&providers, Path::new("/src/repo/lib"));
+        assert_eq!(selected.map(|(p, root)| (p.name(), root)), Some(("jj", PathBuf::from("/src/repo"))));
+    }
+
+    #[test]
+    fn test_nearest_root_wins() {
+        let providers = providers([Some("/src/repo"), Some("/src/repo/vendor/dep")]);
+        let selected = select_provider(&providers, Path::new("/src/repo/vendor/dep"));
+        assert_eq!(selected.map(|(p, _)| p.name()), Some("git"));
+    }
+
+    #[test]
+    fn test_no_repository() {
+        let providers = providers([None, None]);
+        assert!(select_provider(&providers, Path::new("/tmp")).is_none());
+    }
+
+    #[test]
+    fn test_markers_and_divergence() {
+        let status = VcsStatus {
+            dirty: true,
+            staged: true,
+            untracked: true,
+            ahead: 3,
+            ..VcsStatus::new("git", "1234567")
+        };
+        assert!(status.has_changes());
+        assert_eq!(status.markers(), "+*?");
+        assert_eq!(status.divergence(), Some("↑3".to_string()));
+        assert_eq!(status.format(), "1234567 ↑3+*?");
+    }
+
+    #[test]
+    fn test_stale_and_pending_format() {
+        let stale = VcsStatus {
+            dirty: true,
+            stale: true,
+            ..VcsStatus::new("jj", "abc123")
+        };
+        assert_eq!(stale.format(), "abc123* …");
+        assert_eq!(VcsStatus::pending("jj").format(), "…");
+    }
+
+    #[test]
+    fn test_timed_out_status_falls_back_to_last_known() {
+        let repo = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let providers: Vec<Arc<dyn VcsProvider>> = vec![Arc::new(HangingProvider {
+            root: repo.path().to_path_buf(),
+        })];
+        let path = repo.path().to_str().unwrap();
+
+        // Nothing known yet: a placeholder rather than no segment at all
+        let cache = VcsCache::new(cache_dir.path(), Duration::ZERO);
+        let deadline = Instant::now() + Duration::from_millis(20);
+        assert_eq!(
+            status_from(&providers, path, deadline, Some(&cache)),
+            Some(VcsStatus::pending("git").with_root(repo.path()))
+        );
+
+        let last_known = VcsStatus {
+            refs: vec!["main".to_string()],
+            ..VcsStatus::new("git", "1234567")
+        };
+        cache.store(repo.path(), &last_known, vcs_cache::stamps(repo.path()));
+
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let status = status_from(&providers, path, deadline, Some(&cache)).unwrap();
+        assert!(status.stale);
+        assert_eq!(status.format(), "1234567 main …");
+    }
+
+    #[test]
+    fn test_finished_status_is_remembered() {
+        let repo = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let initialized = std::process::Command::new("git")
+            .args(["init", "-q", "-b", "main"])
+            .current_dir(repo.path())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !initialized {
+            return;
+        }
+        let path = repo.path().to_str().unwrap();
+        let cache = VcsCache::new(cache_dir.path(), Duration::ZERO);
+
+        let status = get_vcs_status(path, Instant::now() + Duration::from_secs(30), Some(&cache)).unwrap();
+        assert!(!status.stale);
+        assert_eq!(status.refs, vec!["main".to_string()]);
+
+        // The same repository, now too slow to answer
+        let providers: Vec<Arc<dyn VcsProvider>> = vec![Arc::new(HangingProvider {
+            root: repo.path().to_path_buf(),
+        })];
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let status = status_from(&providers, path, deadline, Some(&cache)).unwrap();
+        assert!(status.stale);
+        assert_eq!(status.refs, vec!["main".to_string()]);
+    }
+
+    #[test]
+    fn test_jj_status_is_cached_across_probes() {
+        let repo = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let settings = UserSettings::from_config(StackedConfig::empty());
+        Workspace::init_internal_git(&settings, repo.path()).unwrap();
+        let path = repo.path().to_str().unwrap();
+        let cache = VcsCache::new(cache_dir.path(), Duration::from_secs(60));
+
+        // Probing snapshots the working copy, which mustn't look like a change
+        let probed = get_vcs_status(path, Instant::now() + Duration::from_secs(30), Some(&cache)).unwrap();
+        assert_eq!(probed.backend, "jj");
+        assert_eq!(cache.fresh(repo.path(), &vcs_cache::stamps(repo.path())), Some(probed.clone()));
+
+        let providers: Vec<Arc<dyn VcsProvider>> = vec![Arc::new(HangingProvider {
+            root: repo.path().to_path_buf(),
+        })];
+        let status = status_from(&providers, path, Instant::now() + Duration::from_millis(20), Some(&cache));
+        assert_eq!(status, Some(probed));
+    }
+
+    #[test]
+    fn test_fresh_cache_entry_skips_probing() {
+        let repo = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(repo.path().join(".git")).unwrap();
+        let providers: Vec<Arc<dyn VcsProvider>> = vec![Arc::new(HangingProvider {
+            root: repo.path().to_path_buf(),
+        })];
+        let path = repo.path().to_str().unwrap();
+        let cache = VcsCache::new(cache_dir.path(), Duration::from_secs(60));
+
+        // The provider never finishes, so only the cache can produce this
+        let cached = VcsStatus::new("git", "1234567");
+        cache.store(repo.path(), &cached, vcs_cache::stamps(repo.path()));
+        let status = status_from(&providers, path, Instant::now() + Duration::from_millis(20), Some(&cache));
+        assert_eq!(status, Some(cached));
+
+        // Touching the index invalidates it
+        std::thread::sleep(Duration::from_millis(10));
+        std::fs::write(repo.path().join(".git/index"), "").unwrap();
+        let status = status_from(&providers, path, Instant::now() + Duration::from_millis(20), Some(&cache)).unwrap();
+        assert!(status.stale);
+    }
+
+    #[test]
+    fn test_status_knows_its_root() {
+        let providers = providers([Some("/src/billing"), None]);
+        let status = status_from(&providers, "/src/billing/api", Instant::now() + Duration::from_secs(5), None).unwrap();
+        assert_eq!(status.root, Some(PathBuf::from("/src/billing")));
+        assert_eq!(status.repo_name().as_deref(), Some("billing"));
+    }
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cache;
+use crate::git_status::git_dir;
+use crate::vcs::VcsStatus;
+
+/// Files and directories, relative to the repository root, whose mtimes
+/// change when the status is likely to: a jj operation or checkout, or files
+/// appearing at the top level. Edits to tracked files don't touch any of
+/// them; the TTL bounds how long those go unseen.
+///
+/// Not the `.jj/working_copy` directory: every snapshot, our own included,
+/// creates and removes a lock file in it.
+const STAMPED_PATHS: &[&str] = &["", ".jj/repo/op_heads/heads", ".jj/working_copy/checkout"];
+
+/// Modification times (nanoseconds since the epoch) of the stamped paths
+/// that exist
+pub type Stamps = BTreeMap<String, u64>;
+
+pub fn stamps(root: &Path) -> Stamps {
+    let mut paths: Vec<(String, PathBuf)> = STAMPED_PATHS
+        .iter()
+        .map(|path| (path.to_string(), root.join(path)))
+        .collect();
+
+    // A git commit, checkout or `add`. In a worktree `.git` is a file
+    // pointing at its own git directory, and branches live in the main one.
+    if let Some(git_dir) = git_dir(root) {
+        let common_dir = fs::read_to_string(git_dir.join("commondir"))
+            .map(|dir| git_dir.join(dir.trim()))
+            .unwrap_or_else(|_| git_dir.clone());
+        paths.push((".git/HEAD".to_string(), git_dir.join("HEAD")));
+        paths.push((".git/index".to_string(), git_dir.join("index")));
+        paths.push((".git/refs/heads".to_string(), common_dir.join("refs/heads")));
+    }
+
+    paths
+        .into_iter()
+        .filter_map(|(key, path)| {
+            let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+            let nanos = modified.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+            Some((key, nanos as u64))
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CacheEntry {
+    /// The repository the entry is for, since file names are only hashes
+    root: PathBuf,
+    status: VcsStatus,
+    stamps: Stamps,
+    /// Milliseconds since the epoch
+    written: u64,
+}
+
+/// VCS status per repository root, kept on disk between runs. An entry is
+/// reused without probing while the repository's stamps are unchanged and
+/// it's younger than `ttl`; older entries are still shown, marked stale,
+/// when probing runs out of time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VcsCache {
+    pub dir: PathBuf,
+    pub ttl: Duration,
+}
+
+impl VcsCache {
+    pub fn new(cache_dir: &Path, ttl: Duration) -> Self {
+        Self {
+            dir: cache_dir.join("vcs"),
+            ttl,
+        }
+    }
+
+    /// The cached status for `root`, if nothing changed since it was stored
+    pub fn fresh(&self, root: &Path, stamps: &Stamps) -> Option<VcsStatus> {
+        let entry = self.read(root)?;
+        let age = now_millis().checked_sub(entry.written)?;
+        (&entry.stamps == stamps && u128::from(age) < self.ttl.as_millis()).then_some(entry.status)
+    }
+
+    /// The last status stored for `root`, however old
+    pub fn last_known(&self, root: &Path) -> Option<VcsStatus> {
+        self.read(root).map(|entry| entry.status)
+    }
+
+    /// Remembers `status` along with the stamps taken before collecting it,
+    /// so changes made while collecting invalidate the entry
+    pub fn store(&self, root: &Path, status: &VcsStatus, stamps: Stamps) {
+        let entry = CacheEntry {
+            root: root.to_path_buf(),
+            status: status.clone(),
+            stamps,
+            written: now_millis(),
+        };
+        if let Ok(bytes) = serde_json::to_vec(&entry) {
+            let _ = cache::write_atomic(&self.entry_path(root), &bytes);
+        }
+    }
+
+    /// The entry stored for `root`; one left by another root whose path
+    /// hashes the same isn't
+    fn read(&self, root: &Path) -> Option<CacheEntry> {
+        let bytes = fs::read(self.entry_path(root)).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&bytes).ok()?;
+        (entry.root == root).then_some(entry)
+    }
+
+    /// One file per repository root, named by a hash of the root's path
+    fn entry_path(&self, root: &Path) -> PathBuf {
+        let key = format!("{:016x}", fnv1a(root.as_os_str().as_encoded_bytes()));
+        self.dir.join(cache::file_name(&key, "json"))
+    }
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher`, it hashes the same on every Rust
+/// release, so entries keep their names across toolchain upgrades.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status() -> VcsStatus {
+        VcsStatus {
+            refs: vec!["main".to_string()],
+            ..VcsStatus::new("jj", "abc123")
+        }
+    }
+
+    #[test]
+    fn test_fresh_while_stamps_match() {
+        let repo = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(repo.path().join(".jj/repo/op_heads/heads")).unwrap();
+        let cache = VcsCache::new(cache_dir.path(), Duration::from_secs(60));
+
+        let stamps = stamps(repo.path());
+        assert!(stamps.contains_key(".jj/repo/op_heads/heads"));
+        assert!(!stamps.contains_key(".git/index"));
+        assert_eq!(cache.fresh(repo.path(), &stamps), None);
+
+        cache.store(repo.path(), &status(), stamps.clone());
+        assert_eq!(cache.fresh(repo.path(), &stamps), Some(status()));
+
+        // A new jj operation moves the op heads
+        let mut changed = stamps;
+        *changed.get_mut(".jj/repo/op_heads/heads").unwrap() += 1;
+        assert_eq!(cache.fresh(repo.path(), &changed), None);
+        assert_eq!(cache.last_known(repo.path()), Some(status()));
+    }
+
+    #[test]
+    fn test_worktree_stamps_its_git_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let main_git = dir.path().join("main/.git");
+        let worktree_git = main_git.join("worktrees/feature");
+        std::fs::create_dir_all(main_git.join("refs/heads")).unwrap();
+        std::fs::create_dir_all(&worktree_git).unwrap();
+        std::fs::write(worktree_git.join("HEAD"), "ref: refs/heads/feature\n").unwrap();
+        std::fs::write(worktree_git.join("commondir"), "../..\n").unwrap();
+        let root = dir.path().join("feature");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join(".git"), format!("gitdir: {}\n", worktree_git.display())).unwrap();
+
+        let before = stamps(&root);
+        assert!(before.contains_key(".git/HEAD"));
+        assert!(before.contains_key(".git/refs/heads"));
+
+        // Switching the worktree's branch
+        std::thread::sleep(Duration::from_millis(10));
+        std::fs::write(worktree_git.join("HEAD"), "ref: refs/heads/other\n").unwrap();
+        assert_ne!(stamps(&root), before);
+    }
+
+    #[test]
+    fn test_expired_after_ttl() {
+        let repo = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let stamps = stamps(repo.path());
+
+        let cache = VcsCache::new(cache_dir.path(), Duration::ZERO);
+        cache.store(repo.path(), &status(), stamps.clone());
+        assert_eq!(cache.fresh(repo.path(), &stamps), None);
+        assert_eq!(cache.last_known(repo.path()), Some(status()));
+    }
+
+    #[test]
+    fn test_entry_for_another_root_is_not_reused() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = VcsCache::new(cache_dir.path(), Duration::from_secs(60));
+        cache.store(Path::new("/src/a"), &status(), Stamps::new());
+
+        // As if /src/b's name hashed to the same file
+        std::fs::rename(cache.entry_path(Path::new("/src/a")), cache.entry_path(Path::new("/src/b"))).unwrap();
+        assert_eq!(cache.fresh(Path::new("/src/b"), &Stamps::new()), None);
+        assert_eq!(cache.last_known(Path::new("/src/b")), None);
+    }
+
+    #[test]
+    fn test_entry_names_are_stable() {
+        // Published FNV-1a test vectors
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        let cache = VcsCache::new(Path::new("/cache"), Duration::ZERO);
+        assert_eq!(
+            cache.entry_path(Path::new("/src/a")),
+            PathBuf::from(format!("/cache/vcs/{:016x}.json", fnv1a(b"/src/a")))
+        );
+    }
+
+    #[test]
+    fn test_entries_are_per_root() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = VcsCache::new(cache_dir.path(), Duration::from_secs(60));
+        cache.store(Path::new("/src/a"), &status(), Stamps::new());
+        assert_eq!(cache.fresh(Path::new("/src/a"), &Stamps::new()), Some(status()));
+        assert_eq!(cache.fresh(Path::new("/src/b"), &Stamps::new()), None);
+    }
+}
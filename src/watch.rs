@@ -0,0 +1,245 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories that are never watched: VCS internals change on every status
+/// refresh (jj writes snapshot objects), which would trigger the next one
+const ALWAYS_SKIPPED: &[&str] = &[".git", ".jj"];
+
+/// Metadata that changes when the user commits, switches branches or runs
+/// any jj operation, relative to the repository root. Watched without
+/// descending into subdirectories.
+const METADATA_DIRS: &[&str] = &[".jj/repo/op_heads/heads", ".git", ".git/refs/heads"];
+
+/// Directory names to leave unwatched under `root`: the VCS internals plus
+/// plain names from the root `.gitignore` such as `target` or
+/// `node_modules`, since build output churns without changing status.
+/// Patterns with globs or inner slashes are left to the periodic refresh.
+fn skipped_names(root: &Path) -> Vec<String> {
+    let mut names: Vec<String> = ALWAYS_SKIPPED.iter().map(|name| name.to_string()).collect();
+    if let Ok(gitignore) = fs::read_to_string(root.join(".gitignore")) {
+        for line in gitignore.lines() {
+            let pattern = line.trim().trim_matches('/');
+            let is_plain_name = !pattern.is_empty()
+                && !pattern.starts_with(['#', '!'])
+                && !pattern.contains(['/', '*', '?', '[', '\\']);
+            if is_plain_name {
+                names.push(pattern.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Every directory of the working tree under `dir`, skipping `skipped` names
+fn tree_dirs(dir: &Path, skipped: &[String], dirs: &mut Vec<PathBuf>) {
+    dirs.push(dir.to_path_buf());
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+        let name = entry.file_name();
+        if is_dir && !skipped.iter().any(|skip| name == skip.as_str()) {
+            tree_dirs(&entry.path(), skipped, dirs);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use rustix::fs::inotify::{self, CreateFlags, ReadFlags, WatchFlags};
+    use std::collections::HashMap;
+    use std::ffi::OsStr;
+    use std::io;
+    use std::mem::MaybeUninit;
+    use std::os::fd::OwnedFd;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+
+    use super::{skipped_names, tree_dirs, METADATA_DIRS};
+
+    struct Watch {
+        root: PathBuf,
+        dir: PathBuf,
+        recursive: bool,
+    }
+
+    /// Watches repositories with inotify, which isn't recursive: every
+    /// directory of the working tree gets a watch of its own
+    pub struct Watcher {
+        fd: OwnedFd,
+        watches: Mutex<HashMap<i32, Watch>>,
+    }
+
+    impl Watcher {
+        pub fn new() -> io::Result<Self> {
+            Ok(Self {
+                fd: inotify::init(CreateFlags::CLOEXEC)?,
+                watches: Mutex::new(HashMap::new()),
+            })
+        }
+
+        /// Watches the working tree and VCS metadata of the repository at
+        /// `root`. Stops quietly at the system's watch limit; changes under
+        /// unwatched directories are picked up by the periodic refresh.
+        pub fn watch(&self, root: &Path) {
+            let skipped = skipped_names(root);
+            let mut dirs = Vec::new();
+            tree_dirs(root, &skipped, &mut dirs);
+
+            for dir in dirs {
+                if self.add(root, &dir, true).is_err() {
+                    break;
+                }
+            }
+            for metadata in METADATA_DIRS {
+                let dir = root.join(metadata);
+                if dir.is_dir() {
+                    let _ = self.add(root, &dir, false);
+                }
+            }
+        }
+
+        pub fn unwatch(&self, root: &Path) {
+            let mut watches = self.watches.lock().unwrap();
+            watches.retain(|&wd, watch| {
+                let keep = watch.root != root;
+                if !keep {
+                    let _ = inotify::remove_watch(&self.fd, wd);
+                }
+                keep
+            });
+        }
+
+        fn add(&self, root: &Path, dir: &Path, recursive: bool) -> io::Result<()> {
+            let flags = WatchFlags::CREATE
+                | WatchFlags::DELETE
+                | WatchFlags::MODIFY
+                | WatchFlags::MOVED_FROM
+                | WatchFlags::MOVED_TO
+                | WatchFlags::ONLYDIR;
+            let wd = inotify::add_watch(&self.fd, dir, flags)?;
+            let watch = Watch {
+                root: root.to_path_buf(),
+                dir: dir.to_path_buf(),
+                recursive,
+            };
+            self.watches.lock().unwrap().insert(wd, watch);
+            Ok(())
+        }
+
+        /// Blocks reading events, calling `changed` with the repository root
+        /// each one belongs to. New directories are watched as they appear.
+        pub fn run(&self, changed: &dyn Fn(&Path)) {
+            let mut buffer = [MaybeUninit::uninit(); 8192];
+            let mut reader = inotify::Reader::new(&self.fd, &mut buffer);
+
+            while let Ok(event) = reader.next() {
+                let events = event.events();
+                if events.contains(ReadFlags::IGNORED) {
+                    self.watches.lock().unwrap().remove(&event.wd());
+                    continue;
+                }
+
+                let (root, new_dir) = {
+                    let watches = self.watches.lock().unwrap();
+                    let Some(watch) = watches.get(&event.wd()) else {
+                        continue;
+                    };
+                    let new_dir = match event.file_name() {
+                        Some(name) if watch.recursive && events.contains(ReadFlags::CREATE | ReadFlags::ISDIR) => {
+                            Some(watch.dir.join(OsStr::from_bytes(name.to_bytes())))
+                        }
+                        _ => None,
+                    };
+                    (watch.root.clone(), new_dir)
+                };
+
+                if let Some(new_dir) = new_dir {
+                    let skipped = skipped_names(&root);
+                    if !skipped.iter().any(|skip| new_dir.file_name() == Some(OsStr::new(skip))) {
+                        let mut dirs = Vec::new();
+                        tree_dirs(&new_dir, &skipped, &mut dirs);
+                        for dir in dirs {
+                            let _ = self.add(&root, &dir, true);
+                        }
+                    }
+                }
+
+                changed(&root);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use inotify::Watcher;
+
+/// Without inotify nothing is watched and the daemon relies on its periodic
+/// refresh alone
+#[cfg(not(target_os = "linux"))]
+pub struct Watcher;
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self)
+    }
+
+    pub fn watch(&self, _root: &Path) {}
+
+    pub fn unwatch(&self, _root: &Path) {}
+
+    pub fn run(&self, _changed: &dyn Fn(&Path)) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skipped_names_from_gitignore() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".gitignore"), "/target\nnode_modules/\n# comment\n*.log\nbuild/out\n!keep\n").unwrap();
+        assert_eq!(skipped_names(dir.path()), vec![".git", ".jj", "target", "node_modules"]);
+    }
+
+    #[test]
+    fn test_tree_dirs_skips_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        for sub in ["src/bin", "target/debug", ".git/objects"] {
+            fs::create_dir_all(dir.path().join(sub)).unwrap();
+        }
+
+        let mut dirs = Vec::new();
+        tree_dirs(dir.path(), &[".git".to_string(), "target".to_string()], &mut dirs);
+        dirs.sort();
+        assert_eq!(dirs, vec![dir.path().to_path_buf(), dir.path().join("src"), dir.path().join("src/bin")]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_watcher_reports_changes_in_new_directories() {
+        use std::sync::{mpsc, Arc};
+        use std::time::Duration;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        let watcher = Arc::new(Watcher::new().unwrap());
+        watcher.watch(&root);
+
+        let (sender, receiver) = mpsc::channel();
+        let running = Arc::clone(&watcher);
+        std::thread::spawn(move || running.run(&|root| sender.send(root.to_path_buf()).unwrap()));
+
+        fs::create_dir(root.join("src")).unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), root);
+
+        // Give the watcher a moment to add the new directory's watch
+        std::thread::sleep(Duration::from_millis(100));
+        while receiver.try_recv().is_ok() {}
+        fs::write(root.join("src/lib.rs"), "").unwrap();
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)).unwrap(), root);
+    }
+}
@@ -0,0 +1,125 @@
+use std::env;
+use unicode_width::UnicodeWidthStr;
+
+/// Columns `text` takes in a terminal, by `unicode-width`'s reading of the
+/// Unicode tables. ANSI escape sequences (colors, hyperlinks) take none.
+pub fn display_width(text: &str) -> usize {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            skip_escape(&mut chars);
+        } else {
+            plain.push(c);
+        }
+    }
+    plain.width()
+}
+
+/// Skips the rest of an escape sequence whose `ESC` was just read: CSI
+/// (`ESC [ ... m`) or OSC (`ESC ] ... BEL` or `ESC ] ... ESC \`)
+fn skip_escape(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    match chars.next() {
+        Some('[') => {
+            for c in chars.by_ref() {
+                if ('\x40'..='\x7e').contains(&c) {
+                    break;
+                }
+            }
+        }
+        Some(']') => {
+            while let Some(c) = chars.next() {
+                if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                    break;
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `text` cut to at most `max` columns, ending in `ellipsis` when anything
+/// was cut. Meant for plain text, before any coloring.
+pub fn truncate(text: &str, max: usize, ellipsis: &str) -> String {
+    if display_width(text) <= max {
+        return text.to_string();
+    }
+
+    let ellipsis_width = display_width(ellipsis);
+    if max < ellipsis_width {
+        return String::new();
+    }
+
+    // Measured whole each time, so a character that joins the one before
+    // it (a skin tone, an emoji sequence) isn't counted on its own
+    let mut truncated = String::new();
+    for c in text.chars() {
+        truncated.push(c);
+        // Leave room for the ellipsis
+        if truncated.width() + ellipsis_width > max {
+            truncated.pop();
+            break;
+        }
+    }
+    truncated.push_str(ellipsis);
+    truncated
+}
+
+/// Terminal width from `COLUMNS`, when the shell exported it
+pub fn columns() -> Option<usize> {
+    env::var("COLUMNS").ok()?.trim().parse().ok().filter(|&columns| columns > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_width() {
+        assert_eq!(display_width(""), 0);
+        assert_eq!(display_width("~/src/grabby"), 12);
+    }
+
+    #[test]
+    fn test_escape_sequences_take_no_columns() {
+        assert_eq!(display_width("\x1b[38;2;255;20;147mmain\x1b[0m"), 4);
+        assert_eq!(display_width("\x1b]8;;file:///tmp\x1b\\tmp\x1b]8;;\x1b\\"), 3);
+        assert_eq!(display_width("\x1b]8;;https://example.com\x07link\x1b]8;;\x07"), 4);
+    }
+
+    #[test]
+    fn test_emoji_width() {
+        for icon in ["📂", "🔀", "⚡", "🧠", "🎭", "📊", "💰"] {
+            assert_eq!(display_width(icon), 2, "{}", icon);
+        }
+        // Text presentation unless asked for emoji presentation
+        assert_eq!(display_width("⚠"), 1);
+        assert_eq!(display_width("⚠\u{fe0f}"), 2);
+        assert_eq!(display_width("‧"), 1);
+        assert_eq!(display_width("…"), 1);
+        // Skin tones and joined sequences are one glyph
+        assert_eq!(display_width("👍🏽"), 2);
+        assert_eq!(display_width("👩\u{200d}💻"), 2);
+    }
+
+    #[test]
+    fn test_cjk_width() {
+        assert_eq!(display_width("日本語"), 6);
+        assert_eq!(display_width("한국어"), 6);
+        assert_eq!(display_width("ＡＢ"), 4);
+        assert_eq!(display_width("e\u{301}"), 1); // Combining accent
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("feature-branch", 20, "…"), "feature-branch");
+        assert_eq!(truncate("feature-branch", 8, "…"), "feature…");
+        assert_eq!(truncate("feature-branch", 8, "..."), "featu...");
+        assert_eq!(truncate("日本語", 4, "…"), "日…");
+        assert_eq!(truncate("日本語", 5, "…"), "日本…");
+        assert_eq!(truncate("main", 0, "…"), "");
+        // A skin tone stays with its emoji
+        assert_eq!(truncate("👍🏽👍🏽 ok", 4, "…"), "👍🏽…");
+    }
+}